@@ -0,0 +1,295 @@
+use std::collections::BTreeMap;
+
+use chrono::NaiveDateTime;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::log::{Entry, Payload, Spawn};
+use crate::resolve::PlayerResolver;
+
+/// The game and round of an entry, as known while streaming through the entries in order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MatchContext {
+    /// The time stamp of the level start of the game.
+    pub game_start: Option<NaiveDateTime>,
+    pub game_mode: String,
+    pub map: String,
+    /// The one-based number of the round, 0 before the first round started.
+    pub round_no: u8,
+    /// The players listed at the start of the round.
+    pub roster: Vec<Spawn>,
+    pub test_drive: bool,
+}
+
+impl MatchContext {
+    /// Updates the context with the entry, before the entry is passed to the analyzers.
+    pub fn update(&mut self, entry: &Entry) {
+        match entry.message {
+            Payload::GameStart(ref start) => {
+                *self = Self {
+                    game_start: Some(entry.time_stamp),
+                    game_mode: start.game_mode.clone(),
+                    ..Self::default()
+                };
+            }
+            Payload::TestStart => self.test_drive = true,
+            Payload::RoundStart(ref start) => {
+                self.game_mode = start.game_mode.clone();
+                self.map = start.map.clone();
+                self.round_no += 1;
+                self.roster.clear();
+            }
+            Payload::Spawn(ref spawn) => {
+                self.roster.retain(|s| s.player_no != spawn.player_no);
+                self.roster.push(spawn.clone());
+            }
+            Payload::RoundFinish(ref finish) if finish.round != 0 => self.round_no = finish.round,
+            _ => {}
+        }
+    }
+
+    pub fn resolver(&self) -> PlayerResolver<'_> {
+        PlayerResolver::new(&self.roster)
+    }
+}
+
+/// A table of values by key, such as the damage dealt by each player.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub name: String,
+    pub columns: Vec<String>,
+    /// The values of each key, in order of the columns.
+    pub rows: BTreeMap<String, Vec<f64>>,
+}
+
+impl Report {
+    pub fn new(name: &str, columns: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            rows: BTreeMap::new(),
+        }
+    }
+
+    /// Adds the value to the column of the key.
+    pub fn add(&mut self, key: &str, column: usize, value: f64) {
+        let width = self.columns.len();
+        self.rows
+            .entry(key.to_string())
+            .or_insert_with(|| vec![0.0; width])[column] += value;
+    }
+}
+
+/// A metric computed in a single pass over the entries.
+pub trait Analyzer {
+    fn on_entry(&mut self, entry: &Entry, context: &MatchContext);
+    fn finish(self: Box<Self>) -> Report;
+}
+
+/// Creates a new analyzer.
+pub type AnalyzerFactory = fn() -> Box<dyn Analyzer>;
+
+/// The analyzers by name.
+#[derive(Debug, Clone, Default)]
+pub struct AnalyzerRegistry {
+    factories: BTreeMap<String, AnalyzerFactory>,
+}
+
+impl AnalyzerRegistry {
+    /// A registry of the built-in `damage`, `kills` and `scores` analyzers.
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::default();
+        registry.register("damage", || Box::new(DamageAnalyzer::default()));
+        registry.register("kills", || Box::new(KillAnalyzer::default()));
+        registry.register("scores", || Box::new(ScoreAnalyzer::default()));
+        registry
+    }
+
+    /// Registers the analyzer, replacing an analyzer of the same name.
+    pub fn register(&mut self, name: &str, factory: AnalyzerFactory) {
+        self.factories.insert(name.to_string(), factory);
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.factories.keys().map(String::as_str)
+    }
+
+    /// Creates the analyzers with the names, or returns the first unknown name.
+    pub fn create<'a>(
+        &self,
+        names: impl IntoIterator<Item = &'a str>,
+    ) -> Result<Vec<Box<dyn Analyzer>>, &'a str> {
+        names
+            .into_iter()
+            .map(|name| self.factories.get(name).map(|f| f()).ok_or(name))
+            .collect()
+    }
+}
+
+/// Runs all analyzers in a single pass over the entries, which must be ordered by time. Returns the reports in order of the analyzers.
+pub fn run_analyzers<'a>(
+    mut analyzers: Vec<Box<dyn Analyzer>>,
+    entries: impl IntoIterator<Item = &'a Entry>,
+) -> Vec<Report> {
+    let mut context = MatchContext::default();
+    for entry in entries {
+        context.update(entry);
+        for analyzer in analyzers.iter_mut() {
+            analyzer.on_entry(entry, &context);
+        }
+        if let Payload::TestFinish = entry.message {
+            context.test_drive = false;
+        }
+    }
+    analyzers.into_iter().map(|a| a.finish()).collect()
+}
+
+/// The damage dealt to others, the hits and the damage received by each player. Test drives are ignored.
+#[derive(Debug, Clone)]
+pub struct DamageAnalyzer {
+    report: Report,
+}
+
+impl Default for DamageAnalyzer {
+    fn default() -> Self {
+        Self {
+            report: Report::new("damage", &["dealt", "hits", "received"]),
+        }
+    }
+}
+
+impl Analyzer for DamageAnalyzer {
+    fn on_entry(&mut self, entry: &Entry, context: &MatchContext) {
+        if let Payload::Damage(ref damage) = entry.message {
+            if context.test_drive || damage.attacker == damage.victim {
+                return;
+            }
+            self.report.add(&damage.attacker, 0, damage.value as f64);
+            self.report.add(&damage.attacker, 1, 1.0);
+            self.report.add(&damage.victim, 2, damage.value as f64);
+        }
+    }
+
+    fn finish(self: Box<Self>) -> Report {
+        self.report
+    }
+}
+
+/// The kills, deaths and assists of each player. Test drives are ignored.
+#[derive(Debug, Clone)]
+pub struct KillAnalyzer {
+    report: Report,
+}
+
+impl Default for KillAnalyzer {
+    fn default() -> Self {
+        Self {
+            report: Report::new("kills", &["kills", "deaths", "assists"]),
+        }
+    }
+}
+
+impl Analyzer for KillAnalyzer {
+    fn on_entry(&mut self, entry: &Entry, context: &MatchContext) {
+        if context.test_drive {
+            return;
+        }
+        match entry.message {
+            Payload::Kill(ref kill) => {
+                if kill.killer != kill.victim {
+                    self.report.add(&kill.killer, 0, 1.0);
+                }
+                self.report.add(&kill.victim, 1, 1.0);
+            }
+            Payload::Assist(ref assist) => self.report.add(&assist.assistant, 2, 1.0),
+            _ => {}
+        }
+    }
+
+    fn finish(self: Box<Self>) -> Report {
+        self.report
+    }
+}
+
+/// The total score and the number of score lines of each player. Test drives are ignored.
+#[derive(Debug, Clone)]
+pub struct ScoreAnalyzer {
+    report: Report,
+}
+
+impl Default for ScoreAnalyzer {
+    fn default() -> Self {
+        Self {
+            report: Report::new("scores", &["score", "lines"]),
+        }
+    }
+}
+
+impl Analyzer for ScoreAnalyzer {
+    fn on_entry(&mut self, entry: &Entry, context: &MatchContext) {
+        if context.test_drive {
+            return;
+        }
+        if let Payload::Score(ref score) = entry.message {
+            self.report.add(&score.nick_name, 0, score.value as f64);
+            self.report.add(&score.nick_name, 1, 1.0);
+        }
+    }
+
+    fn finish(self: Box<Self>) -> Report {
+        self.report
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixture;
+
+    #[test]
+    fn test_run_builtin_analyzers() {
+        let entries = fixture::entries(
+            "20:00:00.000| ====== starting level 3: 'levels/maps/bad_rock' pvp ======
+            20:00:05.000| ===== Gameplay 'Pvp' started, map 'bad_rock' ======
+            20:00:05.000| player  0, uid 11, party 0, nickname: Foo, team: 1, bot: 0, ur: 5, mmHash: ab
+            20:00:05.000| player  1, uid 12, party 0, nickname: Bar, team: 2, bot: 0, ur: 6, mmHash: cd
+            20:00:30.000| Damage. Victim: Bar, attacker: Foo, weapon 'Gun', damage: 30.0 DMG_DIRECT
+            20:00:31.000| Damage. Victim: Bar, attacker: Foo, weapon 'Gun', damage: 20.0 DMG_DIRECT
+            20:00:31.000| Kill. Victim: Bar killer: Foo
+            20:00:31.000| Score: player: 0, nick: Foo, Got: 15, reason: KILL",
+        );
+        let registry = AnalyzerRegistry::with_builtins();
+        assert!(registry.create(["damage", "unknown"]).is_err());
+        let analyzers = registry.create(registry.names()).unwrap();
+        let reports = run_analyzers(analyzers, &entries);
+        assert_eq!(reports.len(), 3);
+        assert_eq!(reports[0].rows["Foo"], vec![50.0, 2.0, 0.0]);
+        assert_eq!(reports[0].rows["Bar"], vec![0.0, 0.0, 50.0]);
+        assert_eq!(reports[1].rows["Bar"], vec![0.0, 1.0, 0.0]);
+        assert_eq!(reports[2].rows["Foo"], vec![15.0, 1.0]);
+    }
+
+    #[test]
+    fn test_analyzers_skip_test_drives() {
+        let entries = fixture::entries(
+            "20:00:00.000| ====== TestDrive started ======
+            20:00:10.000| Damage. Victim: Dummy, attacker: Foo, weapon 'Gun', damage: 30.0 DMG_DIRECT
+            20:00:11.000| Kill. Victim: Dummy killer: Foo
+            20:00:11.000| Score: player: 0, nick: Foo, Got: 15, reason: KILL
+            20:00:20.000| ====== TestDrive finish ======
+            20:01:00.000| ====== starting level 3: 'levels/maps/bad_rock' pvp ======
+            20:01:05.000| ===== Gameplay 'Pvp' started, map 'bad_rock' ======
+            20:01:05.000| player  0, uid 11, party 0, nickname: Foo, team: 1, bot: 0, ur: 5, mmHash: ab
+            20:01:05.000| player  1, uid 12, party 0, nickname: Bar, team: 2, bot: 0, ur: 6, mmHash: cd
+            20:01:30.000| Damage. Victim: Bar, attacker: Foo, weapon 'Gun', damage: 20.0 DMG_DIRECT",
+        );
+        let registry = AnalyzerRegistry::with_builtins();
+        let analyzers = registry.create(["damage", "kills", "scores"]).unwrap();
+        let reports = run_analyzers(analyzers, &entries);
+        assert!(!reports[0].rows.contains_key("Dummy"));
+        assert_eq!(reports[0].rows["Foo"], vec![20.0, 1.0, 0.0]);
+        assert!(reports[1].rows.is_empty());
+        assert!(reports[2].rows.is_empty());
+    }
+}
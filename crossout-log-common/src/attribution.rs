@@ -0,0 +1,214 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::NaiveDateTime;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::game::Round;
+use crate::log::{Damage, Payload};
+
+/// Who did the work for a kill.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct KillAttribution {
+    pub time_stamp: NaiveDateTime,
+    pub killer: String,
+    pub victim: String,
+    /// The weapon of the last hit of the killer on the victim, or of the last hit on the victim if the killer did not deal damage.
+    pub finishing_weapon: Option<String>,
+    /// The time between the first damage on the victim and the kill.
+    pub time_to_kill_sec: f32,
+    /// The damage dealt to the victim in the tick of the kill, after the kill was logged.
+    pub overkill: f32,
+    /// The attackers of the victim, most damage first.
+    pub contributions: Vec<Contribution>,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Contribution {
+    pub attacker: String,
+    /// The damage dealt to the victim since its previous death.
+    pub damage: f32,
+    /// The share of all damage dealt to the victim.
+    pub share: f32,
+    /// The time between the last hit of the attacker and the kill.
+    pub last_hit_sec: f32,
+    /// The damage by weapon.
+    pub weapons: BTreeMap<String, f32>,
+    /// The `Assist` line of the attacker following the kill, if the game logged one.
+    pub assist: Option<LoggedAssist>,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoggedAssist {
+    pub elapsed_sec: f32,
+    pub damage_dealt: f32,
+}
+
+impl KillAttribution {
+    /// The contributions of the attackers other than the killer, which the game did not log an assist for.
+    pub fn unlogged_assists(&self) -> impl Iterator<Item = &Contribution> {
+        self.contributions
+            .iter()
+            .filter(move |c| c.attacker != self.killer && c.damage > 0.0 && c.assist.is_none())
+    }
+
+    /// The logged assists without any damage dealt in the log.
+    pub fn unmatched_assists(&self) -> impl Iterator<Item = &Contribution> {
+        self.contributions
+            .iter()
+            .filter(|c| c.damage == 0.0 && c.assist.is_some())
+    }
+}
+
+/// Attributes every kill of the round to the damage dealt to the victim since its previous death.
+pub fn attribute_kills(round: &Round) -> Vec<KillAttribution> {
+    let entries = &round.entries;
+    // the index of the first entry after the previous death of a player
+    let mut life_start = HashMap::<&str, usize>::new();
+    let mut attributions = Vec::new();
+    for (pos, entry) in entries.iter().enumerate() {
+        let kill = match entry.message {
+            Payload::Kill(ref kill) => kill,
+            _ => continue,
+        };
+        let from = life_start
+            .insert(kill.victim.as_str(), pos + 1)
+            .unwrap_or(0);
+        let hits: Vec<(NaiveDateTime, &Damage)> = entries[from..pos]
+            .iter()
+            .filter_map(|e| match e.message {
+                Payload::Damage(ref d) if d.victim == kill.victim && d.attacker != d.victim => {
+                    Some((e.time_stamp, d))
+                }
+                _ => None,
+            })
+            .collect();
+        let total: f32 = hits.iter().map(|(_, d)| d.value).sum();
+
+        let mut contributions: Vec<Contribution> = Vec::new();
+        for &(time_stamp, damage) in hits.iter() {
+            let last_hit_sec = sec_between(time_stamp, entry.time_stamp);
+            let known = contributions
+                .iter()
+                .position(|c| c.attacker == damage.attacker);
+            let contribution = match known {
+                Some(i) => &mut contributions[i],
+                None => {
+                    contributions.push(Contribution {
+                        attacker: damage.attacker.clone(),
+                        damage: 0.0,
+                        share: 0.0,
+                        last_hit_sec,
+                        weapons: BTreeMap::new(),
+                        assist: None,
+                    });
+                    contributions.last_mut().unwrap()
+                }
+            };
+            contribution.damage += damage.value;
+            contribution.last_hit_sec = last_hit_sec;
+            *contribution
+                .weapons
+                .entry(damage.weapon.clone())
+                .or_default() += damage.value;
+        }
+
+        // the assists and the remaining damage of the tick are logged after the kill
+        let mut overkill = 0.0;
+        for next in entries[pos + 1..].iter() {
+            match next.message {
+                Payload::Assist(ref assist) => {
+                    let logged = LoggedAssist {
+                        elapsed_sec: assist.elapsed_sec,
+                        damage_dealt: assist.damage_dealt,
+                    };
+                    match contributions
+                        .iter_mut()
+                        .find(|c| c.attacker == assist.assistant)
+                    {
+                        Some(contribution) => contribution.assist = Some(logged),
+                        None => contributions.push(Contribution {
+                            attacker: assist.assistant.clone(),
+                            damage: 0.0,
+                            share: 0.0,
+                            last_hit_sec: assist.elapsed_sec,
+                            weapons: BTreeMap::new(),
+                            assist: Some(logged),
+                        }),
+                    }
+                }
+                Payload::Damage(ref d) if next.time_stamp == entry.time_stamp => {
+                    if d.victim == kill.victim && d.attacker != d.victim {
+                        overkill += d.value;
+                    }
+                }
+                _ => break,
+            }
+        }
+
+        for contribution in contributions.iter_mut() {
+            contribution.share = if total > 0.0 {
+                contribution.damage / total
+            } else {
+                0.0
+            };
+        }
+        contributions.sort_by(|lhs, rhs| rhs.damage.total_cmp(&lhs.damage));
+        let finishing_weapon = hits
+            .iter()
+            .rev()
+            .find(|(_, d)| d.attacker == kill.killer)
+            .or_else(|| hits.last())
+            .map(|(_, d)| d.weapon.clone());
+        attributions.push(KillAttribution {
+            time_stamp: entry.time_stamp,
+            killer: kill.killer.clone(),
+            victim: kill.victim.clone(),
+            finishing_weapon,
+            time_to_kill_sec: hits
+                .first()
+                .map(|&(first, _)| sec_between(first, entry.time_stamp))
+                .unwrap_or_default(),
+            overkill,
+            contributions,
+        });
+    }
+    attributions
+}
+
+fn sec_between(from: NaiveDateTime, to: NaiveDateTime) -> f32 {
+    (to - from).num_milliseconds() as f32 / 1000.0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixture;
+
+    #[test]
+    fn test_attribute_kill() {
+        let games = fixture::games(
+            "20:00:00.000| ====== starting level 3: 'levels/maps/bad_rock' pvp ======
+            20:00:05.000| ===== Gameplay 'Pvp' started, map 'bad_rock' ======
+            20:00:20.000| Damage. Victim: Bar, attacker: Baz, weapon 'Cannon', damage: 30.0 DMG_DIRECT
+            20:00:25.000| Damage. Victim: Bar, attacker: Foo, weapon 'Gun', damage: 10.0 DMG_DIRECT
+            20:00:30.000| Damage. Victim: Bar, attacker: Foo, weapon 'Rocket', damage: 10.0 DMG_BLAST
+            20:00:30.000| Kill. Victim: Bar killer: Foo
+            20:00:30.000| Damage. Victim: Bar, attacker: Foo, weapon 'Rocket', damage: 5.0 DMG_BLAST
+            20:00:30.000|      assist by Baz weapon: 'Cannon', 10.0 sec ago, damage: 30.0 DMG_DIRECT",
+        );
+        let kills = attribute_kills(&games[0].rounds[0]);
+        assert_eq!(kills.len(), 1);
+        let kill = &kills[0];
+        assert_eq!(kill.finishing_weapon.as_deref(), Some("Rocket"));
+        assert_eq!(kill.time_to_kill_sec, 10.0);
+        assert_eq!(kill.overkill, 5.0);
+        assert_eq!(kill.contributions[0].attacker, "Baz");
+        assert_eq!(kill.contributions[0].share, 0.6);
+        assert!(kill.contributions[0].assist.is_some());
+        assert_eq!(kill.unlogged_assists().count(), 0);
+    }
+}
@@ -0,0 +1,527 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{NaiveDateTime, NaiveTime};
+use flagset::FlagSet;
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take_while1};
+use nom::character::complete::{char, multispace0};
+use nom::combinator::{all_consuming, map, map_opt, value};
+use nom::multi::many0;
+use nom::sequence::{delimited, preceded, terminated, tuple};
+
+use crate::log::{DamageFlag, Entry, Payload};
+
+/// A predicate over log entries, such as `type == Damage && attacker == "Foo" && flags contains BLAST && time > 20:00`.
+///
+/// A comparison is a field, an operator and a literal. The fields are `type`, the name of the payload, `time`, the time stamp, and the fields of the payloads in `log.rs`.
+/// The operators are `==`, `!=`, `<`, `<=`, `>`, `>=` and `contains`, which tests for a substring or a damage flag.
+/// Literals are numbers, times of the day `20:00:30.5`, date times `2022-06-01T20:00`, quoted strings and bare words such as `Damage`, `KILL` or `BLAST`.
+/// Comparisons are combined with `&&`, `||`, `!` and parentheses. A comparison on a field the payload does not have is false.
+/// A literal must have the type of the field, so strings consisting of digits are quoted: `victim == "1234"`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    Compare(String, Op, Literal),
+    Not(Box<Filter>),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Op::Eq => "==",
+            Op::Ne => "!=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+            Op::Contains => "contains",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Str(String),
+    Num(f64),
+    Time(NaiveTime),
+    DateTime(NaiveDateTime),
+    Flag(DamageFlag),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterError {
+    /// The expression is malformed at the byte offset.
+    Syntax(usize),
+    UnknownField(String),
+    UnknownFlag(String),
+    /// The field is compared with a literal of another type.
+    TypeMismatch(String, FieldType),
+    /// The operator is not defined for the type of the field.
+    InvalidOperator(String, Op),
+}
+
+/// The type of the values of a field, and of the literals it is compared with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    Str,
+    Num,
+    Time,
+    Flags,
+}
+
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FieldType::Str => "a string",
+            FieldType::Num => "a number",
+            FieldType::Time => "a time",
+            FieldType::Flags => "a damage flag",
+        })
+    }
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilterError::Syntax(position) => write!(f, "Invalid filter at position {}", position),
+            FilterError::UnknownField(field) => write!(f, "Unknown field `{}`", field),
+            FilterError::UnknownFlag(flag) => write!(f, "Unknown damage flag `{}`", flag),
+            FilterError::TypeMismatch(field, expected) => {
+                write!(f, "`{}` must be compared with {}", field, expected)
+            }
+            FilterError::InvalidOperator(field, op) => {
+                write!(f, "`{}` cannot be compared with `{}`", field, op)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FilterError {}
+
+/// The fields of all payloads.
+pub const FIELDS: &[&str] = &[
+    "type",
+    "time",
+    "level_no",
+    "level_name",
+    "game_mode",
+    "map",
+    "round",
+    "finish_reason",
+    "winning_team",
+    "win_reason",
+    "duration_sec",
+    "player_no",
+    "user_id",
+    "party_id",
+    "nick_name",
+    "team",
+    "bot",
+    "session",
+    "spawn_counter",
+    "design_hash",
+    "value",
+    "reason",
+    "victim",
+    "attacker",
+    "killer",
+    "assistant",
+    "weapon",
+    "flags",
+    "damage_flags",
+    "elapsed_sec",
+    "damage_dealt",
+    "name",
+];
+
+/// The value of a field of an entry.
+#[derive(Debug, Clone, PartialEq)]
+enum Field<'a> {
+    Str(Cow<'a, str>),
+    Num(f64),
+    Time(NaiveDateTime),
+    Flags(FlagSet<DamageFlag>),
+}
+
+impl FromStr for Filter {
+    type Err = FilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (_, filter) = all_consuming(terminated(expression, multispace0))(s).map_err(|e| {
+            let rest = match e {
+                nom::Err::Error(e) | nom::Err::Failure(e) => e.input,
+                nom::Err::Incomplete(_) => "",
+            };
+            FilterError::Syntax(s.len() - rest.len())
+        })?;
+        filter.validate()
+    }
+}
+
+impl Filter {
+    pub fn matches(&self, entry: &Entry) -> bool {
+        match self {
+            Filter::Compare(name, op, literal) => {
+                field(entry, name).is_some_and(|field| compare(&field, *op, literal))
+            }
+            Filter::Not(filter) => !filter.matches(entry),
+            Filter::And(lhs, rhs) => lhs.matches(entry) && rhs.matches(entry),
+            Filter::Or(lhs, rhs) => lhs.matches(entry) || rhs.matches(entry),
+        }
+    }
+
+    /// Checks the field names, the types of the literals and the operators, and reads the literals compared with damage flags as flags.
+    fn validate(self) -> Result<Self, FilterError> {
+        Ok(match self {
+            Filter::Compare(name, op, literal) => {
+                if !FIELDS.contains(&name.as_str()) {
+                    return Err(FilterError::UnknownField(name));
+                }
+                let field_type = field_type(&name);
+                let literal = match (field_type, literal) {
+                    (FieldType::Flags, Literal::Str(flag)) => {
+                        Literal::Flag(parse_flag(&flag).ok_or(FilterError::UnknownFlag(flag))?)
+                    }
+                    (FieldType::Str, literal @ Literal::Str(_))
+                    | (FieldType::Num, literal @ Literal::Num(_))
+                    | (FieldType::Time, literal @ (Literal::Time(_) | Literal::DateTime(_))) => {
+                        literal
+                    }
+                    _ => return Err(FilterError::TypeMismatch(name, field_type)),
+                };
+                let valid = match field_type {
+                    FieldType::Str => true,
+                    FieldType::Num | FieldType::Time => op != Op::Contains,
+                    FieldType::Flags => matches!(op, Op::Eq | Op::Ne | Op::Contains),
+                };
+                if !valid {
+                    return Err(FilterError::InvalidOperator(name, op));
+                }
+                Filter::Compare(name, op, literal)
+            }
+            Filter::Not(filter) => Filter::Not(Box::new(filter.validate()?)),
+            Filter::And(lhs, rhs) => {
+                Filter::And(Box::new(lhs.validate()?), Box::new(rhs.validate()?))
+            }
+            Filter::Or(lhs, rhs) => {
+                Filter::Or(Box::new(lhs.validate()?), Box::new(rhs.validate()?))
+            }
+        })
+    }
+}
+
+/// The type of a field in [`FIELDS`], as read by [`field`].
+fn field_type(name: &str) -> FieldType {
+    match name {
+        "time" => FieldType::Time,
+        "flags" | "damage_flags" => FieldType::Flags,
+        "level_no" | "round" | "winning_team" | "duration_sec" | "player_no" | "user_id"
+        | "party_id" | "team" | "bot" | "session" | "spawn_counter" | "design_hash" | "value"
+        | "elapsed_sec" | "damage_dealt" => FieldType::Num,
+        _ => FieldType::Str,
+    }
+}
+
+/// A damage flag by its name in the log, with or without the `DMG_` prefix.
+fn parse_flag(name: &str) -> Option<DamageFlag> {
+    let name = name.to_uppercase();
+    name.parse()
+        .or_else(|_| format!("DMG_{}", name).parse())
+        .ok()
+}
+
+/// The name of the variant of the payload.
+pub fn payload_type(payload: &Payload) -> &'static str {
+    match payload {
+        Payload::GameStart(_) => "GameStart",
+        Payload::TestStart => "TestStart",
+        Payload::TestFinish => "TestFinish",
+        Payload::Player(_) => "Player",
+        Payload::RoundStart(_) => "RoundStart",
+        Payload::RoundFinish(_) => "RoundFinish",
+        Payload::BattleStart => "BattleStart",
+        Payload::Spawn(_) => "Spawn",
+        Payload::Score(_) => "Score",
+        Payload::Damage(_) => "Damage",
+        Payload::Stripe(_) => "Stripe",
+        Payload::Kill(_) => "Kill",
+        Payload::Assist(_) => "Assist",
+    }
+}
+
+fn field<'a>(entry: &'a Entry, name: &str) -> Option<Field<'a>> {
+    let str = |s: &'a String| Some(Field::Str(Cow::Borrowed(s.as_str())));
+    let num = |n: f64| Some(Field::Num(n));
+    match (&entry.message, name) {
+        (payload, "type") => Some(Field::Str(Cow::Borrowed(payload_type(payload)))),
+        (_, "time") => Some(Field::Time(entry.time_stamp)),
+        (Payload::GameStart(start), "level_no") => num(start.level_no as f64),
+        (Payload::GameStart(start), "level_name") => str(&start.level_name),
+        (Payload::GameStart(start), "game_mode") => str(&start.game_mode),
+        (Payload::Player(player), "player_no") => num(player.player_no as f64),
+        (Payload::Player(player), "nick_name") => str(&player.nick_name),
+        (Payload::Player(player), "team") => num(player.team as f64),
+        (Payload::Player(player), "spawn_counter") => num(player.spawn_counter as f64),
+        (Payload::Player(player), "design_hash") => num(player.design_hash as f64),
+        (Payload::RoundStart(start), "game_mode") => str(&start.game_mode),
+        (Payload::RoundStart(start), "map") => str(&start.map),
+        (Payload::RoundFinish(finish), "round") => num(finish.round as f64),
+        (Payload::RoundFinish(finish), "finish_reason") => {
+            Some(Field::Str(Cow::Owned(finish.finish_reason.to_string())))
+        }
+        (Payload::RoundFinish(finish), "winning_team") => num(finish.winning_team as f64),
+        (Payload::RoundFinish(finish), "win_reason") => {
+            Some(Field::Str(Cow::Owned(finish.win_reason.to_string())))
+        }
+        (Payload::RoundFinish(finish), "duration_sec") => num(finish.duration_sec as f64),
+        (Payload::Spawn(spawn), "player_no") => num(spawn.player_no as f64),
+        (Payload::Spawn(spawn), "user_id") => num(spawn.user_id as f64),
+        (Payload::Spawn(spawn), "party_id") => num(spawn.party_id as f64),
+        (Payload::Spawn(spawn), "nick_name") => str(&spawn.nick_name),
+        (Payload::Spawn(spawn), "team") => num(spawn.team as f64),
+        (Payload::Spawn(spawn), "bot") => num(spawn.bot as f64),
+        (Payload::Spawn(spawn), "session") => num(spawn.session as f64),
+        (Payload::Spawn(spawn), "design_hash") => num(spawn.design_hash as f64),
+        (Payload::Score(score), "player_no") => num(score.player_no as f64),
+        (Payload::Score(score), "nick_name") => str(&score.nick_name),
+        (Payload::Score(score), "value") => num(score.value as f64),
+        (Payload::Score(score), "reason") => Some(Field::Str(Cow::Owned(score.reason.to_string()))),
+        (Payload::Damage(damage), "victim") => str(&damage.victim),
+        (Payload::Damage(damage), "attacker") => str(&damage.attacker),
+        (Payload::Damage(damage), "weapon") => str(&damage.weapon),
+        (Payload::Damage(damage), "value") => num(damage.value as f64),
+        (Payload::Damage(damage), "flags") => Some(Field::Flags(damage.flags)),
+        (Payload::Stripe(stripe), "name") => str(&stripe.name),
+        (Payload::Stripe(stripe), "value") => num(stripe.value as f64),
+        (Payload::Stripe(stripe), "player_no") => num(stripe.player_no as f64),
+        (Payload::Stripe(stripe), "nick_name") => str(&stripe.nick_name),
+        (Payload::Kill(kill), "victim") => str(&kill.victim),
+        (Payload::Kill(kill), "killer") => str(&kill.killer),
+        (Payload::Assist(assist), "assistant") => str(&assist.assistant),
+        (Payload::Assist(assist), "weapon") => str(&assist.weapon),
+        (Payload::Assist(assist), "elapsed_sec") => num(assist.elapsed_sec as f64),
+        (Payload::Assist(assist), "damage_dealt") => num(assist.damage_dealt as f64),
+        (Payload::Assist(assist), "damage_flags") => Some(Field::Flags(assist.damage_flags)),
+        _ => None,
+    }
+}
+
+fn compare(field: &Field, op: Op, literal: &Literal) -> bool {
+    use std::cmp::Ordering;
+
+    let ordering = match (field, literal) {
+        (Field::Str(s), Literal::Str(l)) => {
+            if op == Op::Contains {
+                return s.contains(l.as_str());
+            }
+            Some(s.as_ref().cmp(l.as_str()))
+        }
+        (Field::Num(n), Literal::Num(l)) => n.partial_cmp(l),
+        (Field::Time(t), Literal::Time(l)) => Some(t.time().cmp(l)),
+        (Field::Time(t), Literal::DateTime(l)) => Some(t.cmp(l)),
+        (Field::Flags(flags), Literal::Flag(flag)) => {
+            return match op {
+                Op::Contains => flags.contains(*flag),
+                Op::Eq => *flags == FlagSet::from(*flag),
+                Op::Ne => *flags != FlagSet::from(*flag),
+                _ => false,
+            };
+        }
+        _ => None,
+    };
+    match (ordering, op) {
+        (Some(ordering), Op::Eq) => ordering == Ordering::Equal,
+        (Some(ordering), Op::Ne) => ordering != Ordering::Equal,
+        (Some(ordering), Op::Lt) => ordering == Ordering::Less,
+        (Some(ordering), Op::Le) => ordering != Ordering::Greater,
+        (Some(ordering), Op::Gt) => ordering == Ordering::Greater,
+        (Some(ordering), Op::Ge) => ordering != Ordering::Less,
+        _ => false,
+    }
+}
+
+type ParseResult<'a, T> = nom::IResult<&'a str, T>;
+
+fn ws<'a, T>(
+    parser: impl FnMut(&'a str) -> ParseResult<'a, T>,
+) -> impl FnMut(&'a str) -> ParseResult<'a, T> {
+    preceded(multispace0, parser)
+}
+
+fn expression(input: &str) -> ParseResult<'_, Filter> {
+    let (input, first) = conjunction(input)?;
+    let (input, rest) = many0(preceded(ws(tag("||")), conjunction))(input)?;
+    let filter = rest
+        .into_iter()
+        .fold(first, |lhs, rhs| Filter::Or(Box::new(lhs), Box::new(rhs)));
+    Ok((input, filter))
+}
+
+fn conjunction(input: &str) -> ParseResult<'_, Filter> {
+    let (input, first) = negation(input)?;
+    let (input, rest) = many0(preceded(ws(tag("&&")), negation))(input)?;
+    let filter = rest
+        .into_iter()
+        .fold(first, |lhs, rhs| Filter::And(Box::new(lhs), Box::new(rhs)));
+    Ok((input, filter))
+}
+
+fn negation(input: &str) -> ParseResult<'_, Filter> {
+    alt((
+        map(preceded(ws(char('!')), negation), |f| {
+            Filter::Not(Box::new(f))
+        }),
+        delimited(ws(char('(')), expression, ws(char(')'))),
+        comparison,
+    ))(input)
+}
+
+fn comparison(input: &str) -> ParseResult<'_, Filter> {
+    map(
+        tuple((ws(word), ws(operator), ws(literal))),
+        |(field, op, literal)| Filter::Compare(field.to_string(), op, literal),
+    )(input)
+}
+
+fn word(input: &str) -> ParseResult<'_, &str> {
+    take_while1(|c: char| c.is_alphanumeric() || c == '_')(input)
+}
+
+fn operator(input: &str) -> ParseResult<'_, Op> {
+    alt((
+        value(Op::Eq, tag("==")),
+        value(Op::Ne, tag("!=")),
+        value(Op::Le, tag("<=")),
+        value(Op::Ge, tag(">=")),
+        value(Op::Lt, tag("<")),
+        value(Op::Gt, tag(">")),
+        value(Op::Contains, tag("contains")),
+    ))(input)
+}
+
+fn literal(input: &str) -> ParseResult<'_, Literal> {
+    alt((
+        map(quoted, Literal::Str),
+        map_opt(
+            take_while1(|c: char| c.is_alphanumeric() || "_:.-+".contains(c)),
+            bare_literal,
+        ),
+    ))(input)
+}
+
+/// A string in double quotes, where `\"` and `\\` are escaped.
+fn quoted(input: &str) -> ParseResult<'_, String> {
+    let (rest, _) = char('"')(input)?;
+    let mut value = String::new();
+    let mut chars = rest.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((&rest[i + 1..], value)),
+            '\\' => match chars.next() {
+                Some((_, c)) => value.push(c),
+                None => break,
+            },
+            c => value.push(c),
+        }
+    }
+    Err(nom::Err::Error(nom::error::Error::new(
+        input,
+        nom::error::ErrorKind::Char,
+    )))
+}
+
+fn bare_literal(token: &str) -> Option<Literal> {
+    if token.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+        return Some(Literal::Str(token.to_string()));
+    }
+    ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(token, format).ok())
+        .map(Literal::DateTime)
+        .or_else(|| {
+            ["%H:%M:%S%.f", "%H:%M"]
+                .iter()
+                .find_map(|format| NaiveTime::parse_from_str(token, format).ok())
+                .map(Literal::Time)
+        })
+        .or_else(|| token.parse().ok().map(Literal::Num))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixture;
+
+    #[test]
+    fn test_filter_entries() {
+        let entries = fixture::entries(
+            "19:59:59.000| Damage. Victim: Bar, attacker: Foo, weapon 'Gun', damage: 30.0 DMG_BLAST
+            20:00:30.000| Damage. Victim: Bar, attacker: Foo, weapon 'Gun', damage: 20.0 DMG_BLAST|CONTACT
+            20:00:31.000| Damage. Victim: Foo, attacker: Bar, weapon 'Gun', damage: 10.0 DMG_DIRECT
+            20:00:31.000| Kill. Victim: Bar killer: Foo",
+        );
+        let count = |filter: &str| {
+            let filter: Filter = filter.parse().unwrap();
+            entries.iter().filter(|e| filter.matches(e)).count()
+        };
+        assert_eq!(
+            count(r#"type == Damage && attacker == "Foo" && flags contains BLAST && time > 20:00"#),
+            1
+        );
+        assert_eq!(count("type == Damage && (value >= 20 || victim != Bar)"), 3);
+        assert_eq!(count("!(type == Damage) || killer == Foo"), 1);
+        assert_eq!(count("time < 2022-06-01T20:00 || weapon contains un"), 3);
+        assert_eq!(count(r#"attacker == "Fo\"o""#), 0);
+        assert_eq!(
+            "type == Damage &&".parse::<Filter>(),
+            Err(FilterError::Syntax(15))
+        );
+        assert_eq!(
+            "colour == red".parse::<Filter>(),
+            Err(FilterError::UnknownField("colour".to_string()))
+        );
+        assert_eq!(
+            "flags contains FOO".parse::<Filter>(),
+            Err(FilterError::UnknownFlag("FOO".to_string()))
+        );
+        assert_eq!(
+            "player_no == Foo".parse::<Filter>(),
+            Err(FilterError::TypeMismatch(
+                "player_no".to_string(),
+                FieldType::Num
+            ))
+        );
+        assert_eq!(
+            "attacker > 20:00".parse::<Filter>(),
+            Err(FilterError::TypeMismatch(
+                "attacker".to_string(),
+                FieldType::Str
+            ))
+        );
+        assert_eq!(
+            "time contains 20:00".parse::<Filter>(),
+            Err(FilterError::InvalidOperator(
+                "time".to_string(),
+                Op::Contains
+            ))
+        );
+        assert_eq!(
+            "flags > BLAST".parse::<Filter>(),
+            Err(FilterError::InvalidOperator("flags".to_string(), Op::Gt))
+        );
+    }
+}
@@ -0,0 +1,22 @@
+//! Logs written inline in the tests.
+
+use chrono::NaiveDate;
+
+use crate::game::{assemble_games, Game};
+use crate::log::{parse_entry, Entry};
+
+/// Parses the lines of the log, dated 2022-06-01. The indentation of the lines is ignored.
+pub fn entries(log: &str) -> Vec<Entry> {
+    let date = NaiveDate::from_ymd_opt(2022, 6, 1).unwrap();
+    log.lines()
+        .map(|line| match parse_entry::<()>(date)(line.trim()) {
+            Ok((_, entry)) => entry,
+            Err(e) => panic!("Invalid line `{}`: {:?}", line.trim(), e),
+        })
+        .collect()
+}
+
+/// Parses the log and groups the entries into games.
+pub fn games(log: &str) -> Vec<Game> {
+    assemble_games(entries(log))
+}
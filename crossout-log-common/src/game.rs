@@ -0,0 +1,429 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{Duration, NaiveDateTime};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::log::{Entry, Payload, RoundFinish, Spawn, WinReason};
+
+/// A game assembled from the entries between two `====== starting level` lines.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Game {
+    pub start: NaiveDateTime,
+    pub end: Option<NaiveDateTime>,
+    pub level_no: usize,
+    pub level_name: String,
+    /// The gameplay mode of the rounds, or the level mode if no round was started.
+    pub game_mode: String,
+    pub map: String,
+    /// The game is a `TestDrive` session and not a match.
+    pub test_drive: bool,
+    /// The `Gameplay finish` of the game, if the log contains it.
+    pub finish: Option<RoundFinish>,
+    pub rounds: Vec<Round>,
+}
+
+impl Game {
+    fn new(start: NaiveDateTime) -> Self {
+        Self {
+            start,
+            end: None,
+            level_no: 0,
+            level_name: String::new(),
+            game_mode: String::new(),
+            map: String::new(),
+            test_drive: false,
+            finish: None,
+            rounds: Vec::new(),
+        }
+    }
+
+    /// The time between the level start and the game finish.
+    pub fn duration(&self) -> Option<Duration> {
+        self.end.map(|end| end - self.start)
+    }
+
+    /// The perspective independent identity of the game.
+    pub fn fingerprint(&self) -> Fingerprint {
+        let spawns = self.rounds.iter().flat_map(|r| r.roster.iter());
+        Fingerprint {
+            map: self.map.clone(),
+            game_mode: self.game_mode.clone(),
+            start: self.start,
+            user_ids: spawns
+                .clone()
+                .filter(|s| s.bot == 0)
+                .map(|s| s.user_id)
+                .collect(),
+            sessions: spawns.filter(|s| s.bot == 0).map(|s| s.session).collect(),
+        }
+    }
+
+    /// Whether the game is a best of three series, such as a clan war.
+    pub fn is_series(&self) -> bool {
+        self.rounds
+            .iter()
+            .filter_map(|r| r.finish.as_ref())
+            .chain(self.finish.as_ref())
+            .any(|f| {
+                matches!(
+                    f.win_reason,
+                    WinReason::BestOfThree | WinReason::BestOfThreeTimer
+                )
+            })
+    }
+
+    /// The rounds of the series, or `None` if the game is no series.
+    ///
+    /// Teams may swap sides between rounds, then the team numbers of the roster change. The winners are reported with the team numbers of the first round.
+    pub fn series(&self) -> Option<Series> {
+        if !self.is_series() {
+            return None;
+        }
+        let first = self.rounds.first()?;
+        let mut rounds: Vec<SeriesRound> = Vec::new();
+        for round in self.rounds.iter() {
+            let finish = match round.finish {
+                Some(ref finish) => finish,
+                None => continue,
+            };
+            let side_swapped = is_side_swapped(&first.roster, &round.roster);
+            rounds.push(SeriesRound {
+                round_no: round.round_no,
+                winning_team: finish.winning_team,
+                winner: first_round_team(finish.winning_team, side_swapped),
+                win_reason: finish.win_reason,
+                side_swapped,
+            });
+        }
+        let mut wins = BTreeMap::new();
+        for round in rounds.iter() {
+            *wins.entry(round.winner).or_default() += 1;
+        }
+        // the game finish reports the winner with the team numbers of the last round
+        let winner = match (&self.finish, rounds.last()) {
+            (Some(finish), Some(last)) => {
+                Some(first_round_team(finish.winning_team, last.side_swapped))
+            }
+            _ => wins
+                .iter()
+                .max_by_key(|&(_, &won)| won)
+                .map(|(&team, _)| team),
+        };
+        Some(Series {
+            rounds,
+            wins,
+            winner,
+        })
+    }
+
+    /// The round to which the next gameplay entry belongs. Opens a new round, if the previous one is finished.
+    fn open_round(&mut self, time_stamp: NaiveDateTime) -> &mut Round {
+        if self.rounds.last().is_none_or(|r| r.finish.is_some()) {
+            let roster = self
+                .rounds
+                .last()
+                .map(|r| r.roster.clone())
+                .unwrap_or_default();
+            self.rounds.push(Round {
+                round_no: self.rounds.len() as u8 + 1,
+                start: time_stamp,
+                end: None,
+                game_mode: self.game_mode.clone(),
+                map: self.map.clone(),
+                roster,
+                finish: None,
+                entries: Vec::new(),
+            });
+        }
+        self.rounds.last_mut().unwrap()
+    }
+}
+
+/// Identifies a game independent of the player who wrote the log.
+///
+/// Logs of the same game uploaded by different participants differ in the start time, because the level is loaded at different speeds, and in the entries each client records. The map, game mode and the human participants are the same.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fingerprint {
+    pub map: String,
+    pub game_mode: String,
+    pub start: NaiveDateTime,
+    /// The `uid`s of all human players.
+    pub user_ids: BTreeSet<usize>,
+    /// The `ur` values of all human players.
+    pub sessions: BTreeSet<usize>,
+}
+
+impl Fingerprint {
+    /// Whether both fingerprints identify the same game. The start times may differ by at most `tolerance`, and the majority of players must be present in both.
+    pub fn matches(&self, other: &Fingerprint, tolerance: Duration) -> bool {
+        self.map == other.map
+            && self.game_mode == other.game_mode
+            && (self.start - other.start).num_milliseconds().abs() <= tolerance.num_milliseconds()
+            && majority_shared(&self.user_ids, &other.user_ids)
+            && majority_shared(&self.sessions, &other.sessions)
+    }
+}
+
+/// Whether more than half the elements of the smaller set are contained in the other set.
+fn majority_shared(lhs: &BTreeSet<usize>, rhs: &BTreeSet<usize>) -> bool {
+    let shared = lhs.intersection(rhs).count();
+    shared * 2 > lhs.len().min(rhs.len())
+}
+
+/// The rounds of a best of three game.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Series {
+    /// The finished rounds.
+    pub rounds: Vec<SeriesRound>,
+    /// The number of rounds won by each team, using the team numbers of the first round.
+    pub wins: BTreeMap<u8, usize>,
+    /// The team winning the series, using the team numbers of the first round.
+    pub winner: Option<u8>,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeriesRound {
+    pub round_no: u8,
+    /// The winning team as reported by the round finish.
+    pub winning_team: u8,
+    /// The winning team, using the team numbers of the first round.
+    pub winner: u8,
+    pub win_reason: WinReason,
+    /// The teams play on the sides of the opponents in the first round.
+    pub side_swapped: bool,
+}
+
+/// Whether the majority of the players present in both rosters changed the team.
+fn is_side_swapped(first: &[Spawn], roster: &[Spawn]) -> bool {
+    let (mut swapped, mut stayed) = (0, 0);
+    for spawn in roster {
+        let same = |s: &&Spawn| s.user_id == spawn.user_id && s.nick_name == spawn.nick_name;
+        match first.iter().find(same) {
+            Some(s) if s.team != spawn.team => swapped += 1,
+            Some(_) => stayed += 1,
+            None => {}
+        }
+    }
+    swapped > stayed
+}
+
+/// Maps the team number of a round to the team number of the first round. There are two teams, numbered 1 and 2.
+fn first_round_team(team: u8, side_swapped: bool) -> u8 {
+    match (side_swapped, team) {
+        (true, 1) => 2,
+        (true, 2) => 1,
+        _ => team,
+    }
+}
+
+/// A single round of a game. Regular games consist of one round, clan-wars of up to three.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Round {
+    /// The one-based number of the round in the game.
+    pub round_no: u8,
+    pub start: NaiveDateTime,
+    pub end: Option<NaiveDateTime>,
+    pub game_mode: String,
+    pub map: String,
+    /// The players listed at the start of the round.
+    pub roster: Vec<Spawn>,
+    pub finish: Option<RoundFinish>,
+    /// The gameplay entries of the round, in order of occurrence.
+    pub entries: Vec<Entry>,
+}
+
+impl Round {
+    /// Whether any damage was dealt in the round.
+    pub fn is_played(&self) -> bool {
+        self.entries
+            .iter()
+            .any(|e| matches!(e.message, Payload::Damage(_) | Payload::Kill(_)))
+    }
+
+    /// The battle time reported by the round finish, or the time between start and end.
+    pub fn duration_sec(&self) -> Option<f32> {
+        match (&self.finish, self.end) {
+            (Some(finish), _) => Some(finish.duration_sec),
+            (None, Some(end)) => Some((end - self.start).num_milliseconds() as f32 / 1000.0),
+            _ => None,
+        }
+    }
+}
+
+/// Groups the entries into games and rounds. The entries are ordered by time stamp, entries sharing a time stamp retain their order.
+pub fn assemble_games(mut entries: Vec<Entry>) -> Vec<Game> {
+    entries.sort_by_key(|e| e.time_stamp);
+    let mut games = Vec::new();
+    let mut current: Option<Game> = None;
+    for entry in entries {
+        let time_stamp = entry.time_stamp;
+        match entry.message {
+            Payload::GameStart(start) => {
+                games.extend(current.take());
+                let mut game = Game::new(time_stamp);
+                game.level_no = start.level_no;
+                game.level_name = start.level_name;
+                game.game_mode = start.game_mode;
+                current = Some(game);
+            }
+            Payload::TestStart => {
+                current
+                    .get_or_insert_with(|| Game::new(time_stamp))
+                    .test_drive = true;
+            }
+            Payload::TestFinish => {
+                let mut game = current.take().unwrap_or_else(|| Game::new(time_stamp));
+                game.test_drive = true;
+                game.end = Some(time_stamp);
+                games.push(game);
+            }
+            Payload::RoundStart(start) => {
+                let game = current.get_or_insert_with(|| Game::new(time_stamp));
+                if let Some(round) = game.rounds.last_mut().filter(|r| r.finish.is_none()) {
+                    // the previous round did not finish
+                    round.end = Some(time_stamp);
+                }
+                game.game_mode = start.game_mode;
+                game.map = start.map;
+                game.rounds.push(Round {
+                    round_no: game.rounds.len() as u8 + 1,
+                    start: time_stamp,
+                    end: None,
+                    game_mode: game.game_mode.clone(),
+                    map: game.map.clone(),
+                    roster: Vec::new(),
+                    finish: None,
+                    entries: Vec::new(),
+                });
+            }
+            Payload::Spawn(spawn) => {
+                let round = current
+                    .get_or_insert_with(|| Game::new(time_stamp))
+                    .open_round(time_stamp);
+                round.roster.retain(|s| s.player_no != spawn.player_no);
+                round.roster.push(spawn);
+            }
+            Payload::RoundFinish(finish) if finish.round != 0 => {
+                let round = current
+                    .get_or_insert_with(|| Game::new(time_stamp))
+                    .open_round(time_stamp);
+                round.round_no = finish.round;
+                round.end = Some(time_stamp);
+                round.finish = Some(finish);
+            }
+            Payload::RoundFinish(finish) => {
+                let mut game = current.take().unwrap_or_else(|| Game::new(time_stamp));
+                let played = game.rounds.len();
+                if let Some(round) = game.rounds.last_mut().filter(|r| r.finish.is_none()) {
+                    if played > 1 && !round.is_played() {
+                        // the series is decided, the remaining entries belong to the last played round
+                        let remainder = game.rounds.pop().unwrap();
+                        game.rounds
+                            .last_mut()
+                            .unwrap()
+                            .entries
+                            .extend(remainder.entries);
+                    } else {
+                        round.end = Some(time_stamp);
+                        round.finish = Some(finish.clone());
+                    }
+                }
+                game.end = Some(time_stamp);
+                game.finish = Some(finish);
+                games.push(game);
+            }
+            message => {
+                current
+                    .get_or_insert_with(|| Game::new(time_stamp))
+                    .open_round(time_stamp)
+                    .entries
+                    .push(Entry {
+                        time_stamp,
+                        message,
+                    });
+            }
+        }
+    }
+    games.extend(current);
+    games
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixture;
+
+    #[test]
+    fn test_assemble_clan_war() {
+        let games = fixture::games(
+            "20:00:00.000| ====== starting level 3: 'levels/maps/bad_rock' pvp ======
+            20:00:05.000| ===== Gameplay 'Cw' started, map 'bad_rock' ======
+            20:00:05.000| player  0, uid 11, party 1, nickname: Foo, team: 1, bot: 0, ur: 5, mmHash: ab
+            20:00:05.000| player  1, uid 12, party 2, nickname: Bar, team: 2, bot: 0, ur: 6, mmHash: cd
+            20:00:30.000| Damage. Victim: Bar, attacker: Foo, weapon 'Gun', damage: 10.5 DMG_DIRECT
+            20:00:31.000| Kill. Victim: Bar killer: Foo
+            20:02:00.000| ===== Best Of N round 1 finish, reason: no_cars, winner team 1, win reason: BEST_OF_THREE, battle time: 115.0 sec =====
+            20:02:30.000| Damage. Victim: Foo, attacker: Bar, weapon 'Gun', damage: 3.0 DMG_DIRECT
+            20:04:00.000| ===== Best Of N round 2 finish, reason: timer, winner team 1, win reason: BEST_OF_THREE_TIMER, battle time: 90.0 sec =====
+            20:04:01.000| Stripe 'PvpWin' value increased by 1 for player 0 [Foo].
+            20:04:02.000| ===== Gameplay finish, reason: timer, winner team 1, win reason: BEST_OF_THREE_TIMER, battle time: 90.0 sec =====",
+        );
+        assert_eq!(games.len(), 1);
+        let game = &games[0];
+        assert_eq!(game.level_no, 3);
+        assert_eq!(game.game_mode, "Cw");
+        assert_eq!(game.map, "bad_rock");
+        assert_eq!(game.duration(), Some(Duration::seconds(242)));
+        assert_eq!(game.rounds.len(), 2);
+        assert_eq!(game.rounds[0].roster.len(), 2);
+        assert_eq!(game.rounds[0].entries.len(), 2);
+        assert_eq!(game.rounds[1].round_no, 2);
+        assert_eq!(game.rounds[1].roster, game.rounds[0].roster);
+        assert_eq!(game.rounds[1].entries.len(), 2);
+        let series = game.series().unwrap();
+        assert_eq!(series.rounds.len(), 2);
+        assert!(!series.rounds[1].side_swapped);
+        assert_eq!(series.wins[&1], 2);
+        assert_eq!(series.winner, Some(1));
+    }
+
+    #[test]
+    fn test_assemble_clan_war_with_swapped_sides() {
+        let games = fixture::games(
+            "20:00:00.000| ====== starting level 3: 'levels/maps/bad_rock' pvp ======
+            20:00:05.000| ===== Gameplay 'Cw' started, map 'bad_rock' ======
+            20:00:05.000| player  0, uid 11, party 1, nickname: Foo, team: 1, bot: 0, ur: 5, mmHash: ab
+            20:00:05.000| player  1, uid 12, party 2, nickname: Bar, team: 2, bot: 0, ur: 6, mmHash: cd
+            20:02:00.000| ===== Best Of N round 1 finish, reason: no_cars, winner team 1, win reason: BEST_OF_THREE, battle time: 115.0 sec =====
+            20:02:05.000| player  0, uid 11, party 1, nickname: Foo, team: 2, bot: 0, ur: 5, mmHash: ab
+            20:02:05.000| player  1, uid 12, party 2, nickname: Bar, team: 1, bot: 0, ur: 6, mmHash: cd
+            20:04:00.000| ===== Best Of N round 2 finish, reason: no_cars, winner team 1, win reason: BEST_OF_THREE, battle time: 110.0 sec =====
+            20:04:05.000| player  0, uid 11, party 1, nickname: Foo, team: 2, bot: 0, ur: 5, mmHash: ab
+            20:04:05.000| player  1, uid 12, party 2, nickname: Bar, team: 1, bot: 0, ur: 6, mmHash: cd
+            20:06:00.000| ===== Best Of N round 3 finish, reason: no_cars, winner team 1, win reason: BEST_OF_THREE, battle time: 110.0 sec =====
+            20:06:02.000| ===== Gameplay finish, reason: no_cars, winner team 1, win reason: BEST_OF_THREE, battle time: 110.0 sec =====",
+        );
+        assert_eq!(games.len(), 1);
+        let game = &games[0];
+        assert_eq!(game.rounds.len(), 3);
+        assert_eq!(game.rounds[1].owner(11).map(|s| s.team), Some(2));
+        let series = game.series().unwrap();
+        assert!(!series.rounds[0].side_swapped);
+        assert!(series.rounds[1].side_swapped);
+        assert!(series.rounds[2].side_swapped);
+        // team 1 won every round, but from the second round on team 1 is the first round's team 2
+        assert_eq!(
+            series.rounds.iter().map(|r| r.winner).collect::<Vec<_>>(),
+            vec![1, 2, 2]
+        );
+        assert_eq!(series.wins[&1], 1);
+        assert_eq!(series.wins[&2], 2);
+        assert_eq!(series.winner, Some(2));
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::BTreeMap;
+
+use chrono::{Duration, NaiveDateTime};
+#[cfg(feature = "diesel")]
+use diesel_derive_enum::DbEnum;
+#[cfg(feature = "juniper")]
+use juniper::GraphQLEnum;
+use parse_display::{Display, FromStr};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "wundergraph")]
+use wundergraph::query_builder::types::WundergraphValue;
+
+use crate::game::Round;
+use crate::log::Payload;
+
+#[cfg_attr(feature = "diesel", derive(DbEnum))]
+#[cfg_attr(feature = "juniper", derive(GraphQLEnum))]
+#[cfg_attr(
+    feature = "wundergraph",
+    derive(WundergraphValue),
+    sql_type = "HighlightKindMapping"
+)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Display, Clone, Copy, PartialEq, Eq, Hash, FromStr, Debug)]
+pub enum HighlightKind {
+    /// The first kill of the round.
+    #[display("first blood")]
+    FirstBlood,
+    /// Several kills, each within the multi-kill window of the previous one.
+    #[display("multi-kill")]
+    MultiKill,
+    /// Several kills without dying.
+    #[display("streak")]
+    Streak,
+    /// The killer of a team mate was killed shortly after.
+    #[display("trade")]
+    Trade,
+}
+
+/// A noteworthy moment of a round.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Highlight {
+    /// The time of the last kill of the highlight.
+    pub time_stamp: NaiveDateTime,
+    pub kind: HighlightKind,
+    /// The nickname of the player who made the highlight.
+    pub player: String,
+    /// The number of kills of a multi-kill or streak, one otherwise.
+    pub kills: usize,
+    /// The victim of the first blood, or the killer avenged by a trade.
+    pub other: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HighlightConfig {
+    /// The maximum time between two kills of a multi-kill.
+    pub multi_kill_window: Duration,
+    /// The minimum number of kills of a streak.
+    pub streak_min: usize,
+    /// The maximum time between a kill and the death of the killer to count as a trade.
+    pub trade_window: Duration,
+}
+
+impl Default for HighlightConfig {
+    fn default() -> Self {
+        Self {
+            multi_kill_window: Duration::seconds(10),
+            streak_min: 3,
+            trade_window: Duration::seconds(5),
+        }
+    }
+}
+
+/// Detects the highlights among the kills of the round, ordered by time stamp.
+pub fn detect_highlights(round: &Round, config: &HighlightConfig) -> Vec<Highlight> {
+    let kills: Vec<(NaiveDateTime, &str, &str)> = round
+        .entries
+        .iter()
+        .filter_map(|e| match e.message {
+            Payload::Kill(ref kill) => {
+                Some((e.time_stamp, kill.killer.as_str(), kill.victim.as_str()))
+            }
+            _ => None,
+        })
+        .collect();
+    let mut highlights = Vec::new();
+
+    if let Some(&(time_stamp, killer, victim)) = kills.iter().find(|(_, k, v)| k != v) {
+        highlights.push(Highlight {
+            time_stamp,
+            kind: HighlightKind::FirstBlood,
+            player: killer.to_string(),
+            kills: 1,
+            other: Some(victim.to_string()),
+        });
+    }
+
+    let players = round.resolver();
+    let team = |nick_name: &str| players.by_nick(nick_name).map(|s| s.team);
+    // the kills since the last death, and the kills of the current multi-kill, with the time of the last kill
+    let mut streaks = BTreeMap::<&str, (usize, NaiveDateTime)>::new();
+    let mut multis = BTreeMap::<&str, (usize, NaiveDateTime)>::new();
+    for (pos, &(time_stamp, killer, victim)) in kills.iter().enumerate() {
+        if let Some(streak) = streaks.remove(victim) {
+            push_streak(&mut highlights, victim, streak, config);
+        }
+        if let Some(multi) = multis.remove(victim) {
+            push_multi_kill(&mut highlights, victim, multi);
+        }
+        if killer == victim {
+            continue;
+        }
+
+        let streak = streaks.entry(killer).or_insert((0, time_stamp));
+        *streak = (streak.0 + 1, time_stamp);
+        match multis.get_mut(killer) {
+            Some(multi) if time_stamp - multi.1 <= config.multi_kill_window => {
+                *multi = (multi.0 + 1, time_stamp);
+            }
+            _ => {
+                if let Some(previous) = multis.insert(killer, (1, time_stamp)) {
+                    push_multi_kill(&mut highlights, killer, previous);
+                }
+            }
+        }
+
+        // the victim recently killed a team mate of the killer
+        let traded = kills[..pos]
+            .iter()
+            .rev()
+            .take_while(|&&(t, _, _)| time_stamp - t <= config.trade_window)
+            .any(|&(_, k, v)| {
+                k == victim
+                    && v != k
+                    && v != killer
+                    && matches!((team(v), team(killer)), (Some(a), Some(b)) if a == b)
+            });
+        if traded {
+            highlights.push(Highlight {
+                time_stamp,
+                kind: HighlightKind::Trade,
+                player: killer.to_string(),
+                kills: 1,
+                other: Some(victim.to_string()),
+            });
+        }
+    }
+    for (player, streak) in streaks {
+        push_streak(&mut highlights, player, streak, config);
+    }
+    for (player, multi) in multis {
+        push_multi_kill(&mut highlights, player, multi);
+    }
+    highlights.sort_by_key(|h| h.time_stamp);
+    highlights
+}
+
+fn push_streak(
+    highlights: &mut Vec<Highlight>,
+    player: &str,
+    (kills, last): (usize, NaiveDateTime),
+    config: &HighlightConfig,
+) {
+    if kills >= config.streak_min {
+        highlights.push(Highlight {
+            time_stamp: last,
+            kind: HighlightKind::Streak,
+            player: player.to_string(),
+            kills,
+            other: None,
+        });
+    }
+}
+
+fn push_multi_kill(
+    highlights: &mut Vec<Highlight>,
+    player: &str,
+    (kills, last): (usize, NaiveDateTime),
+) {
+    if kills >= 2 {
+        highlights.push(Highlight {
+            time_stamp: last,
+            kind: HighlightKind::MultiKill,
+            player: player.to_string(),
+            kills,
+            other: None,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixture;
+
+    #[test]
+    fn test_detect_highlights() {
+        let games = fixture::games(
+            "20:00:00.000| ====== starting level 3: 'levels/maps/bad_rock' pvp ======
+            20:00:05.000| ===== Gameplay 'Pvp' started, map 'bad_rock' ======
+            20:00:05.000| player  0, uid 11, party 1, nickname: Foo, team: 1, bot: 0, ur: 5, mmHash: ab
+            20:00:05.000| player  1, uid 12, party 2, nickname: Bar, team: 1, bot: 0, ur: 6, mmHash: cd
+            20:00:05.000| player  2, uid 13, party 3, nickname: Baz, team: 2, bot: 0, ur: 7, mmHash: ef
+            20:00:05.000| player  3, uid 14, party 4, nickname: Qux, team: 2, bot: 0, ur: 8, mmHash: 12
+            20:00:05.000| player  4, uid 15, party 5, nickname: Quux, team: 2, bot: 0, ur: 9, mmHash: 34
+            20:00:30.000| Kill. Victim: Bar killer: Baz
+            20:00:32.000| Kill. Victim: Baz killer: Foo
+            20:00:38.000| Kill. Victim: Qux killer: Foo
+            20:00:50.000| Kill. Victim: Quux killer: Foo",
+        );
+        let highlights = detect_highlights(&games[0].rounds[0], &HighlightConfig::default());
+        let kinds: Vec<_> = highlights
+            .iter()
+            .map(|h| (h.kind, h.player.as_str(), h.kills))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (HighlightKind::FirstBlood, "Baz", 1),
+                (HighlightKind::Trade, "Foo", 1),
+                (HighlightKind::MultiKill, "Foo", 2),
+                (HighlightKind::Streak, "Foo", 3),
+            ]
+        );
+
+        // players missing from the roster have no team, so their kills are no trades
+        let games = fixture::games(
+            "20:00:00.000| ====== starting level 3: 'levels/maps/bad_rock' pvp ======
+            20:00:05.000| ===== Gameplay 'Pvp' started, map 'bad_rock' ======
+            20:00:05.000| player  0, uid 11, party 1, nickname: Foo, team: 1, bot: 0, ur: 5, mmHash: ab
+            20:00:30.000| Kill. Victim: Ghost killer: Foo
+            20:00:32.000| Kill. Victim: Foo killer: Phantom",
+        );
+        let highlights = detect_highlights(&games[0].rounds[0], &HighlightConfig::default());
+        assert!(highlights.iter().all(|h| h.kind != HighlightKind::Trade));
+    }
+}
@@ -1 +1,15 @@
+pub mod analyze;
+pub mod attribution;
+pub mod filter;
+pub mod game;
+pub mod highlights;
 pub mod log;
+pub mod owner;
+pub mod party;
+pub mod redact;
+pub mod resolve;
+pub mod scoreboard;
+pub mod testdrive;
+
+#[cfg(test)]
+mod fixture;
@@ -4,19 +4,21 @@ use chrono::{NaiveDate, NaiveTime};
 use diesel_derive_enum::DbEnum;
 use flagset::{flags, FlagSet};
 #[cfg(feature = "juniper")]
-use juniper::{graphql_object, GraphQLInputObject};
+use juniper::GraphQLEnum;
 use nom::branch::alt;
 use nom::bytes::complete::tag;
 use nom::bytes::complete::take;
 use nom::bytes::complete::take_while;
-use nom::character::complete::{digit1, hex_digit1};
-use nom::combinator::{map, opt};
+use nom::character::complete::{digit1, hex_digit1, multispace0, space0, space1};
+use nom::combinator::{eof, map, opt};
 use nom::combinator::{map_res, recognize};
 use nom::sequence::tuple;
 use nom::{AsChar, InputTakeAtPosition};
 use parse_display::{Display, FromStr};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "wundergraph")]
+use wundergraph::query_builder::types::WundergraphValue;
 
 fn parse_time<'a, E>(input: &'a str) -> nom::IResult<&'a str, NaiveTime, E>
 where
@@ -34,7 +36,9 @@ where
     let (input, _) = dot(input)?;
     let (input, milli) = map_res(recognize(digit1), str::parse)(input)?;
 
-    Ok((input, NaiveTime::from_hms_milli(hour, min, sec, milli)))
+    let time = NaiveTime::from_hms_milli_opt(hour, min, sec, milli)
+        .ok_or_else(|| nom::Err::Error(E::from_error_kind(input, nom::error::ErrorKind::Verify)))?;
+    Ok((input, time))
 }
 
 pub fn parse_entry<'a, E>(
@@ -141,7 +145,7 @@ where
     let (input, _) = tag("Spawn player ")(input)?;
     let (input, player_no) = map_res(recognize(digit1), str::parse)(input)?;
     let (input, _) = tag(" [")(input)?;
-    let (input, nick_name) = take_while(|c| c != ']')(input)?;
+    let (input, nick_name) = nick_name_until(tag("], team "))(input)?;
     let (input, _) = tag("], team ")(input)?;
     let (input, team) = map_res(recognize(digit1), str::parse)(input)?;
     let (input, _) = tag(", spawnCounter ")(input)?;
@@ -280,7 +284,7 @@ where
     let (input, _) = tag(", party ")(input)?;
     let (input, party_id) = map_res(recognize(digit1), str::parse)(input)?;
     let (input, _) = tag(", nickname: ")(input)?;
-    let (input, nick_name) = take_while(not_ws_comma)(input)?;
+    let (input, nick_name) = nick_name_until(tuple((space0, tag(", team: "))))(input)?;
     let (input, _) = take_while(char::is_whitespace)(input)?;
     let (input, _) = tag(", team: ")(input)?;
     let (input, team) = map_res(recognize(digit1), str::parse)(input)?;
@@ -325,7 +329,9 @@ where
         tag("nick:"),
         take_while(char::is_whitespace),
     ))(input)?;
-    let (input, nick_name) = take_while(|c: char| c != ',')(input)?;
+    let (input, nick_name) =
+        nick_name_until(tuple((space0, tag(","), space0, tag("Got:"))))(input)?;
+    let (input, _) = space0(input)?;
     let (input, _) = tuple((
         tag(","),
         take_while(char::is_whitespace),
@@ -359,9 +365,9 @@ where
         + nom::error::FromExternalError<&'a str, parse_display::ParseError>,
 {
     let (input, _) = tag("Damage. Victim: ")(input)?;
-    let (input, victim) = take_while(not_ws_comma)(input)?;
+    let (input, victim) = nick_name_until(tuple((space0, tag(", attacker: "))))(input)?;
     let (input, _) = tuple((take_while(char::is_whitespace), tag(", attacker: ")))(input)?;
-    let (input, attacker) = take_while(not_ws_comma)(input)?;
+    let (input, attacker) = nick_name_until(tuple((space0, tag(", weapon '"))))(input)?;
     let (input, _) = tuple((take_while(char::is_whitespace), tag(", weapon '")))(input)?;
     let (input, weapon) = take_while(|c| c != '\'')(input)?;
     let (input, _) = tag("', damage: ")(input)?;
@@ -380,6 +386,32 @@ where
     ))
 }
 
+/// Takes a nickname up to the first position where the separator matches, without consuming the separator.
+///
+/// Nicknames may contain whitespace, commas, brackets and any other character, so they are delimited by the field following them, such as `, team: ` or the end of the line.
+fn nick_name_until<'a, E, O>(
+    mut separator: impl nom::Parser<&'a str, O, E>,
+) -> impl FnMut(&'a str) -> nom::IResult<&'a str, &'a str, E>
+where
+    E: nom::error::ParseError<&'a str>,
+{
+    move |input: &'a str| {
+        let positions = input
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(Some(input.len()));
+        for position in positions {
+            if separator.parse(&input[position..]).is_ok() {
+                return Ok((&input[position..], &input[..position]));
+            }
+        }
+        Err(nom::Err::Error(E::from_error_kind(
+            input,
+            nom::error::ErrorKind::TakeUntil,
+        )))
+    }
+}
+
 fn not_ws_comma(c: char) -> bool {
     !(c.is_whitespace() || c == ',')
 }
@@ -416,7 +448,7 @@ where
     let (input, _) = tag(" for player ")(input)?;
     let (input, player_no) = map_res(recognize(digit1), str::parse)(input)?;
     let (input, _) = tag(" [")(input)?;
-    let (input, nick_name) = take_while(|c| c != ']')(input)?;
+    let (input, nick_name) = nick_name_until(tuple((tag("]."), multispace0, eof)))(input)?;
     let (input, _) = tag("].")(input)?;
     Ok((
         input,
@@ -435,9 +467,9 @@ where
         + nom::error::FromExternalError<&'a str, std::num::ParseIntError>,
 {
     let (input, _) = tag("Kill. Victim: ")(input)?;
-    let (input, victim) = take_while(not_ws)(input)?;
+    let (input, victim) = nick_name_until(tuple((space1, tag("killer: "))))(input)?;
     let (input, _) = tuple((take_while(char::is_whitespace), tag("killer: ")))(input)?;
-    let (input, killer) = take_while(not_ws)(input)?;
+    let (input, killer) = nick_name_until(tuple((multispace0, eof)))(input)?;
     Ok((
         input,
         Kill {
@@ -455,7 +487,7 @@ where
         + nom::error::FromExternalError<&'a str, parse_display::ParseError>,
 {
     let (input, _) = tuple((take_while(char::is_whitespace), tag("assist by ")))(input)?;
-    let (input, assistant) = take_while(not_ws)(input)?;
+    let (input, assistant) = nick_name_until(tuple((space1, tag("weapon: '"))))(input)?;
     let (input, _) = tuple((take_while(char::is_whitespace), tag("weapon: '")))(input)?;
     let (input, weapon) = take_while(|c| c != '\'')(input)?;
     let (input, _) = tag("', ")(input)?;
@@ -477,14 +509,14 @@ where
 }
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Entry {
     pub time_stamp: NaiveDateTime,
     pub message: Payload,
 }
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Player {
     pub player_no: u8,
     pub nick_name: String,
@@ -500,7 +532,7 @@ impl From<Player> for Payload {
 }
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct GameStart {
     pub level_no: usize,
     pub level_name: String,
@@ -514,7 +546,7 @@ impl From<GameStart> for Payload {
 }
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct RoundStart {
     pub game_mode: String,
     pub map: String,
@@ -527,7 +559,7 @@ impl From<RoundStart> for Payload {
 }
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct RoundFinish {
     pub round: u8,
     pub finish_reason: FinishReason,
@@ -543,7 +575,7 @@ impl From<RoundFinish> for Payload {
 }
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Spawn {
     pub player_no: u8,
     pub user_id: usize,
@@ -562,7 +594,7 @@ impl From<Spawn> for Payload {
 }
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Score {
     pub player_no: u8,
     pub nick_name: String,
@@ -577,7 +609,7 @@ impl From<Score> for Payload {
 }
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Damage {
     pub victim: String,
     pub attacker: String,
@@ -593,7 +625,7 @@ impl From<Damage> for Payload {
 }
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Stripe {
     pub name: String,
     pub value: usize,
@@ -608,7 +640,7 @@ impl From<Stripe> for Payload {
 }
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Assist {
     pub assistant: String,
     pub weapon: String,
@@ -624,7 +656,7 @@ impl From<Assist> for Payload {
 }
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Kill {
     pub victim: String,
     pub killer: String,
@@ -637,7 +669,7 @@ impl From<Kill> for Payload {
 }
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Payload {
     GameStart(GameStart),
     TestStart,
@@ -714,8 +746,14 @@ flags! {
 }
 
 #[cfg_attr(feature = "diesel", derive(DbEnum))]
+#[cfg_attr(feature = "juniper", derive(GraphQLEnum))]
+#[cfg_attr(
+    feature = "wundergraph",
+    derive(WundergraphValue),
+    sql_type = "ScoreReasonMapping"
+)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[derive(Display, PartialEq, Eq, FromStr, Debug)]
+#[derive(Display, Clone, Copy, PartialEq, Eq, Hash, FromStr, Debug)]
 pub enum ScoreReason {
     /// FIRST_DAMAGE: First Blood
     #[display("FIRST_DAMAGE")]
@@ -738,8 +776,14 @@ pub enum ScoreReason {
 }
 
 #[cfg_attr(feature = "diesel", derive(DbEnum))]
+#[cfg_attr(feature = "juniper", derive(GraphQLEnum))]
+#[cfg_attr(
+    feature = "wundergraph",
+    derive(WundergraphValue),
+    sql_type = "FinishReasonMapping"
+)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[derive(Display, PartialEq, Eq, FromStr, Debug)]
+#[derive(Display, Clone, Copy, PartialEq, Eq, Hash, FromStr, Debug)]
 pub enum FinishReason {
     /// no_cars: All vehicles are eliminated
     #[display("no_cars")]
@@ -753,8 +797,14 @@ pub enum FinishReason {
 }
 
 #[cfg_attr(feature = "diesel", derive(DbEnum))]
+#[cfg_attr(feature = "juniper", derive(GraphQLEnum))]
+#[cfg_attr(
+    feature = "wundergraph",
+    derive(WundergraphValue),
+    sql_type = "WinReasonMapping"
+)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[derive(Display, PartialEq, Eq, FromStr, Debug)]
+#[derive(Display, Clone, Copy, PartialEq, Eq, Hash, FromStr, Debug)]
 pub enum WinReason {
     /// BEST_OF_THREE: The clan-wars battle is decided
     #[display("BEST_OF_THREE")]
@@ -790,3 +840,57 @@ pub enum WinReason {
     #[display("NONE")]
     None,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The nicknames of the players acting in the entry.
+    fn nick_names(message: Payload) -> Vec<String> {
+        match message {
+            Payload::Player(p) => vec![p.nick_name],
+            Payload::Spawn(s) => vec![s.nick_name],
+            Payload::Score(s) => vec![s.nick_name],
+            Payload::Damage(d) => vec![d.victim, d.attacker],
+            Payload::Stripe(s) => vec![s.nick_name],
+            Payload::Kill(k) => vec![k.victim, k.killer],
+            Payload::Assist(a) => vec![a.assistant],
+            _ => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_parse_tricky_nick_names() {
+        let date = NaiveDate::from_ymd_opt(2022, 6, 1).unwrap();
+        let corpus = [
+            "Foo",
+            "Foo Bar",
+            "Foo,Bar",
+            "Foo, Bar",
+            "[CLAN]Foo",
+            "Foo]",
+            "Foo's",
+            "Ünïcödé_日本",
+            "x_X",
+        ];
+        for nick in corpus {
+            let lines = [
+                format!("20:00:05.000| Spawn player 1 [{}], team 1, spawnCounter 1 , designHash: ab.", nick),
+                format!("20:00:05.000| player  1, uid 12, party 0, nickname: {}    , team: 1, bot: 0, ur: 6, mmHash: cd", nick),
+                format!("20:00:30.000| Score: player: 1, nick: {}, Got: 15, reason: KILL", nick),
+                format!("20:00:30.000| Damage. Victim: {0}   , attacker: {0}, weapon 'Gun', damage: 10.0 DMG_DIRECT", nick),
+                format!("20:00:30.000| Stripe 'PvpKills' value increased by 1 for player 1 [{}].", nick),
+                format!("20:00:30.000| Kill. Victim: {0} killer: {0}  ", nick),
+                format!("20:00:30.000|      assist by {} weapon: 'Cannon', 10.0 sec ago, damage: 30.0 DMG_DIRECT", nick),
+            ];
+            for line in lines {
+                let entry = parse_entry::<()>(date)(&line)
+                    .unwrap_or_else(|_| panic!("failed to parse `{}`", line))
+                    .1;
+                for parsed in nick_names(entry.message) {
+                    assert_eq!(parsed, nick, "in `{}`", line);
+                }
+            }
+        }
+    }
+}
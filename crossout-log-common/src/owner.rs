@@ -0,0 +1,166 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::game::{Game, Round};
+use crate::log::{Payload, Spawn};
+
+/// The evidence that a human player wrote the log.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnerCandidate {
+    pub user_id: usize,
+    /// The nickname in the latest round of the player.
+    pub nick_name: String,
+    /// The number of rounds the player took part in.
+    pub rounds: usize,
+    /// The number of rounds in which the player was the only one receiving `Score`, `Stripe` or `Spawn player` lines, which the client logs for the local player.
+    pub exclusive_rounds: usize,
+}
+
+/// Lists the human players of the matches, most likely log owner first.
+///
+/// The owner takes part in every round of the log, and is usually the only player of a round with score and stripe lines. Test drives have no roster and are ignored.
+pub fn owner_candidates(games: &[Game]) -> Vec<OwnerCandidate> {
+    let mut candidates = BTreeMap::<usize, OwnerCandidate>::new();
+    let rounds = games
+        .iter()
+        .filter(|g| !g.test_drive)
+        .flat_map(|g| g.rounds.iter());
+    for round in rounds {
+        for spawn in round.roster.iter().filter(|s| s.bot == 0) {
+            let candidate = candidates
+                .entry(spawn.user_id)
+                .or_insert_with(|| OwnerCandidate {
+                    user_id: spawn.user_id,
+                    nick_name: String::new(),
+                    rounds: 0,
+                    exclusive_rounds: 0,
+                });
+            candidate.nick_name = spawn.nick_name.clone();
+            candidate.rounds += 1;
+        }
+        if let Some(local) = exclusive_player(round) {
+            if let Some(candidate) = candidates.get_mut(&local.user_id) {
+                candidate.exclusive_rounds += 1;
+            }
+        }
+    }
+    let mut candidates: Vec<OwnerCandidate> = candidates.into_values().collect();
+    candidates.sort_by(|lhs, rhs| {
+        (rhs.exclusive_rounds, rhs.rounds).cmp(&(lhs.exclusive_rounds, lhs.rounds))
+    });
+    candidates
+}
+
+/// The human player who is the only recipient of a kind of line logged for the local player.
+fn exclusive_player(round: &Round) -> Option<&Spawn> {
+    let resolver = round.resolver();
+    let mut recipients = [BTreeSet::new(), BTreeSet::new(), BTreeSet::new()];
+    for entry in round.entries.iter() {
+        let kind = match entry.message {
+            Payload::Score(_) => 0,
+            Payload::Stripe(_) => 1,
+            Payload::Player(_) => 2,
+            _ => continue,
+        };
+        if let Some(spawn) = resolver.actor(&entry.message).filter(|s| s.bot == 0) {
+            recipients[kind].insert(spawn.player_no);
+        }
+    }
+    recipients
+        .iter()
+        .find(|r| r.len() == 1)
+        .and_then(|r| r.iter().next())
+        .and_then(|&player_no| resolver.by_no(player_no))
+}
+
+/// The user id of the player who wrote the log: the configured user id, or the candidate most likely to be the owner.
+///
+/// Returns `None` if no candidate is distinguished, e.g. when two players of a party took part in all rounds and the log has no lines of the local player.
+pub fn infer_owner(games: &[Game], configured: Option<usize>) -> Option<usize> {
+    if configured.is_some() {
+        return configured;
+    }
+    let candidates = owner_candidates(games);
+    let total = games
+        .iter()
+        .filter(|g| !g.test_drive)
+        .map(|g| g.rounds.len())
+        .sum::<usize>();
+    let best = candidates.first()?;
+    let distinguished = match candidates.get(1) {
+        Some(next) => (best.exclusive_rounds, best.rounds) > (next.exclusive_rounds, next.rounds),
+        None => true,
+    };
+    if distinguished && (best.exclusive_rounds > 0 || best.rounds == total) {
+        Some(best.user_id)
+    } else {
+        None
+    }
+}
+
+impl Round {
+    /// The spawn of the log owner, if the owner took part in the round.
+    pub fn owner(&self, user_id: usize) -> Option<&Spawn> {
+        self.roster
+            .iter()
+            .find(|s| s.bot == 0 && s.user_id == user_id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixture;
+
+    #[test]
+    fn test_infer_owner() {
+        let games = fixture::games(
+            "20:00:00.000| ====== starting level 3: 'levels/maps/bad_rock' pvp ======
+            20:00:05.000| ===== Gameplay 'Pvp' started, map 'bad_rock' ======
+            20:00:05.000| player  0, uid 11, party 1, nickname: Foo, team: 1, bot: 0, ur: 5, mmHash: ab
+            20:00:05.000| player  1, uid 12, party 1, nickname: Bar, team: 1, bot: 0, ur: 6, mmHash: cd
+            20:00:05.000| player  2, uid 13, party 3, nickname: Baz, team: 2, bot: 0, ur: 7, mmHash: ef
+            20:00:30.000| Kill. Victim: Baz killer: Foo
+            20:00:30.000| Score: player: 1, nick: Bar, Got: 15, reason: KILL
+            20:10:00.000| ====== starting level 3: 'levels/maps/bad_rock' pvp ======
+            20:10:05.000| ===== Gameplay 'Pvp' started, map 'bad_rock' ======
+            20:10:05.000| player  0, uid 11, party 1, nickname: Foo, team: 1, bot: 0, ur: 5, mmHash: ab
+            20:10:05.000| player  1, uid 12, party 1, nickname: Bar, team: 1, bot: 0, ur: 6, mmHash: cd
+            20:10:05.000| player  2, uid 14, party 4, nickname: Qux, team: 2, bot: 0, ur: 8, mmHash: 12",
+        );
+        let candidates = owner_candidates(&games);
+        assert_eq!(candidates[0].user_id, 12);
+        assert_eq!(candidates[0].exclusive_rounds, 1);
+        assert_eq!(candidates[1].rounds, 2);
+        assert_eq!(infer_owner(&games, None), Some(12));
+        assert_eq!(infer_owner(&games[1..], None), None);
+        assert_eq!(infer_owner(&games[1..], Some(11)), Some(11));
+        assert_eq!(games[0].rounds[0].owner(12).map(|s| s.player_no), Some(1));
+    }
+
+    #[test]
+    fn test_infer_owner_of_tied_party() {
+        let games = fixture::games(
+            "20:00:00.000| ====== starting level 3: 'levels/maps/bad_rock' pvp ======
+            20:00:05.000| ===== Gameplay 'Pvp' started, map 'bad_rock' ======
+            20:00:05.000| player  0, uid 11, party 1, nickname: Foo, team: 1, bot: 0, ur: 5, mmHash: ab
+            20:00:05.000| player  1, uid 12, party 1, nickname: Bar, team: 1, bot: 0, ur: 6, mmHash: cd
+            20:00:05.000| player  2, uid 13, party 3, nickname: Baz, team: 2, bot: 0, ur: 7, mmHash: ef
+            20:00:30.000| Score: player: 0, nick: Foo, Got: 15, reason: KILL
+            20:00:30.000| Score: player: 1, nick: Bar, Got: 15, reason: KILL
+            20:10:00.000| ====== starting level 3: 'levels/maps/bad_rock' pvp ======
+            20:10:05.000| ===== Gameplay 'Pvp' started, map 'bad_rock' ======
+            20:10:05.000| player  0, uid 11, party 1, nickname: Foo, team: 1, bot: 0, ur: 5, mmHash: ab
+            20:10:05.000| player  1, uid 12, party 1, nickname: Bar, team: 1, bot: 0, ur: 6, mmHash: cd
+            20:10:05.000| player  2, uid 14, party 4, nickname: Qux, team: 2, bot: 0, ur: 8, mmHash: 12",
+        );
+        let candidates = owner_candidates(&games);
+        assert_eq!(candidates[0].exclusive_rounds, 0);
+        assert_eq!((candidates[0].rounds, candidates[1].rounds), (2, 2));
+        assert_eq!(infer_owner(&games, None), None);
+        assert_eq!(infer_owner(&games, Some(12)), Some(12));
+    }
+}
@@ -0,0 +1,105 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::game::Round;
+use crate::log::Spawn;
+
+/// A group of players who queued together. Players without a party have the `party_id` 0 and form a party of their own.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Party {
+    pub team: u8,
+    pub party_id: usize,
+    /// The nicknames of the members, in order of the roster.
+    pub members: Vec<String>,
+}
+
+impl Party {
+    pub fn size(&self) -> usize {
+        self.members.len()
+    }
+}
+
+/// Groups the roster into parties, ordered by team and the first member in the roster.
+pub fn parties(roster: &[Spawn]) -> Vec<Party> {
+    let mut parties: Vec<Party> = Vec::new();
+    for spawn in roster {
+        let known = parties
+            .iter_mut()
+            .find(|p| spawn.party_id != 0 && p.team == spawn.team && p.party_id == spawn.party_id);
+        match known {
+            Some(party) => party.members.push(spawn.nick_name.clone()),
+            None => parties.push(Party {
+                team: spawn.team,
+                party_id: spawn.party_id,
+                members: vec![spawn.nick_name.clone()],
+            }),
+        }
+    }
+    parties.sort_by_key(|p| p.team);
+    parties
+}
+
+impl Round {
+    pub fn parties(&self) -> Vec<Party> {
+        parties(&self.roster)
+    }
+
+    /// The party sizes of the team, largest first.
+    pub fn party_sizes(&self, team: u8) -> Vec<usize> {
+        let mut sizes: Vec<usize> = self
+            .parties()
+            .iter()
+            .filter(|p| p.team == team)
+            .map(Party::size)
+            .collect();
+        sizes.sort_unstable_by(|lhs, rhs| rhs.cmp(lhs));
+        sizes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fixture;
+
+    #[test]
+    fn test_parties() {
+        let games = fixture::games(
+            "20:00:00.000| ====== starting level 3: 'levels/maps/bad_rock' pvp ======
+            20:00:05.000| ===== Gameplay 'Pvp' started, map 'bad_rock' ======
+            20:00:05.000| player  0, uid 11, party 5, nickname: Foo, team: 1, bot: 0, ur: 5, mmHash: ab
+            20:00:05.000| player  1, uid 12, party 0, nickname: Bar, team: 1, bot: 0, ur: 6, mmHash: cd
+            20:00:05.000| player  2, uid 13, party 7, nickname: Baz, team: 1, bot: 0, ur: 7, mmHash: ef
+            20:00:05.000| player  3, uid 14, party 5, nickname: Qux, team: 1, bot: 0, ur: 8, mmHash: 12
+            20:00:05.000| player  4, uid 15, party 0, nickname: Quux, team: 1, bot: 0, ur: 9, mmHash: 34
+            20:00:05.000| player  5, uid 16, party 5, nickname: Corge, team: 2, bot: 0, ur: 10, mmHash: 56
+            20:00:05.000| player  6, uid 17, party 0, nickname: Grault, team: 2, bot: 0, ur: 11, mmHash: 78",
+        );
+        let round = &games[0].rounds[0];
+        let parties = round.parties();
+        let members: Vec<(u8, usize, Vec<&str>)> = parties
+            .iter()
+            .map(|p| {
+                (
+                    p.team,
+                    p.party_id,
+                    p.members.iter().map(String::as_str).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            members,
+            vec![
+                (1, 5, vec!["Foo", "Qux"]),
+                (1, 0, vec!["Bar"]),
+                (1, 7, vec!["Baz"]),
+                (1, 0, vec!["Quux"]),
+                (2, 5, vec!["Corge"]),
+                (2, 0, vec!["Grault"]),
+            ]
+        );
+        assert_eq!(round.party_sizes(1), vec![2, 1, 1, 1]);
+        assert_eq!(round.party_sizes(2), vec![1, 1]);
+        assert!(round.party_sizes(3).is_empty());
+    }
+}
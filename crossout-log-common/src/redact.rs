@@ -0,0 +1,290 @@
+use chrono::NaiveDate;
+use sha2::{Digest, Sha256};
+
+use crate::log::{parse_entry, Entry, Payload};
+
+/// Replaces the nicknames, user ids, party ids and sessions of log entries with pseudonyms.
+///
+/// The pseudonyms are derived from a salted hash, so a value is always replaced by the same pseudonym as long as the salt is the same: within a file, or across files sharing the salt. Bots share the user id 0 and players without a party the party id 0, these stay 0.
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    salt: Vec<u8>,
+}
+
+impl Redactor {
+    pub fn new(salt: impl Into<Vec<u8>>) -> Self {
+        Self { salt: salt.into() }
+    }
+
+    fn digest(&self, field: &str, value: &str) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(&self.salt);
+        hasher.update([0]);
+        hasher.update(field.as_bytes());
+        hasher.update([0]);
+        hasher.update(value.as_bytes());
+        hasher.finalize().into()
+    }
+
+    /// The pseudonym of the nickname, a valid nickname itself.
+    pub fn nick_name(&self, nick_name: &str) -> String {
+        if nick_name.is_empty() {
+            return String::new();
+        }
+        let digest = self.digest("nick", nick_name);
+        let hex: String = digest[..4].iter().map(|b| format!("{:02x}", b)).collect();
+        format!("Player_{}", hex)
+    }
+
+    /// The pseudonym of a user id, party id or session, named by `field`.
+    pub fn id(&self, field: &str, id: usize) -> usize {
+        if id == 0 {
+            return 0;
+        }
+        let digest = self.digest(field, &id.to_string());
+        (u32::from_le_bytes([digest[0], digest[1], digest[2], digest[3]]) as usize).max(1)
+    }
+
+    pub fn redact_entry(&self, entry: &mut Entry) {
+        match entry.message {
+            Payload::Player(ref mut player) => {
+                player.nick_name = self.nick_name(&player.nick_name);
+            }
+            Payload::Spawn(ref mut spawn) => {
+                spawn.user_id = self.id("uid", spawn.user_id);
+                spawn.party_id = self.id("party", spawn.party_id);
+                spawn.session = self.id("ur", spawn.session);
+                spawn.nick_name = self.nick_name(&spawn.nick_name);
+            }
+            Payload::Score(ref mut score) => {
+                score.nick_name = self.nick_name(&score.nick_name);
+            }
+            Payload::Damage(ref mut damage) => {
+                damage.victim = self.nick_name(&damage.victim);
+                damage.attacker = self.nick_name(&damage.attacker);
+            }
+            Payload::Stripe(ref mut stripe) => {
+                stripe.nick_name = self.nick_name(&stripe.nick_name);
+            }
+            Payload::Kill(ref mut kill) => {
+                kill.victim = self.nick_name(&kill.victim);
+                kill.killer = self.nick_name(&kill.killer);
+            }
+            Payload::Assist(ref mut assist) => {
+                assist.assistant = self.nick_name(&assist.assistant);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn redact_entries(&self, entries: &mut [Entry]) {
+        for entry in entries {
+            self.redact_entry(entry);
+        }
+    }
+
+    /// Redacts a line of a combat.log, keeping the format and everything but the identities intact.
+    ///
+    /// Lines which cannot be parsed, or whose identities are not found where the parser read them, are masked: only the time stamp is kept. The masked lines are counted in `stats`.
+    pub fn redact_line(&self, line: &str, stats: &mut RedactStats) -> String {
+        // the date is irrelevant, only the identities are replaced
+        let date = NaiveDate::from_ymd_opt(2000, 1, 1).unwrap();
+        let entry = match parse_entry::<()>(date)(line) {
+            Ok((_, entry)) => entry,
+            Err(_) => {
+                stats.unparsed += 1;
+                return mask(line);
+            }
+        };
+        let mut redacted = line.to_string();
+        let nick = |line: &mut String, marker: &str, nick_name: &str| {
+            replace_field(line, marker, nick_name, &self.nick_name(nick_name))
+        };
+        let replaced = match entry.message {
+            Payload::Player(ref player) => nick(&mut redacted, " [", &player.nick_name),
+            Payload::Spawn(ref spawn) => {
+                // in order of the fields, so a marker is never found in a replaced value
+                let user_id = self.id("uid", spawn.user_id).to_string();
+                let party_id = self.id("party", spawn.party_id).to_string();
+                let session = self.id("ur", spawn.session).to_string();
+                replace_field(&mut redacted, "uid ", &spawn.user_id.to_string(), &user_id)
+                    && replace_field(
+                        &mut redacted,
+                        "party ",
+                        &spawn.party_id.to_string(),
+                        &party_id,
+                    )
+                    && nick(&mut redacted, "nickname: ", &spawn.nick_name)
+                    && replace_field(&mut redacted, "ur: ", &spawn.session.to_string(), &session)
+            }
+            Payload::Score(ref score) => nick(&mut redacted, "nick:", &score.nick_name),
+            Payload::Damage(ref damage) => {
+                nick(&mut redacted, "Victim: ", &damage.victim)
+                    && nick(&mut redacted, "attacker: ", &damage.attacker)
+            }
+            Payload::Stripe(ref stripe) => nick(&mut redacted, " [", &stripe.nick_name),
+            Payload::Kill(ref kill) => {
+                nick(&mut redacted, "Victim: ", &kill.victim)
+                    && nick(&mut redacted, "killer: ", &kill.killer)
+            }
+            Payload::Assist(ref assist) => nick(&mut redacted, "assist by ", &assist.assistant),
+            _ => true,
+        };
+        if !replaced {
+            stats.unreplaced += 1;
+            return mask(line);
+        }
+        redacted
+    }
+}
+
+/// The lines masked by [`Redactor::redact_line`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RedactStats {
+    /// Lines which could not be parsed.
+    pub unparsed: usize,
+    /// Lines with an identity which was not found where the parser read it.
+    pub unreplaced: usize,
+}
+
+impl RedactStats {
+    pub fn masked(&self) -> usize {
+        self.unparsed + self.unreplaced
+    }
+}
+
+/// Keeps the time stamp of the line and replaces the rest.
+fn mask(line: &str) -> String {
+    match line.split_once('|') {
+        Some((time_stamp, _))
+            if time_stamp
+                .bytes()
+                .all(|b| b.is_ascii_digit() || b == b':' || b == b'.') =>
+        {
+            format!("{}| {}", time_stamp, MASK)
+        }
+        _ => MASK.to_string(),
+    }
+}
+
+const MASK: &str = "<redacted>";
+
+/// Replaces the value following the first occurrence of the marker and optional whitespace. Returns whether the value was found there.
+fn replace_field(line: &mut String, marker: &str, value: &str, replacement: &str) -> bool {
+    let position = match line.find(marker) {
+        Some(position) => position,
+        None => return false,
+    };
+    let after = position + marker.len();
+    let rest = &line[after..];
+    let start = after + rest.len() - rest.trim_start().len();
+    if !line[start..].starts_with(value) {
+        return false;
+    }
+    line.replace_range(start..start + value.len(), replacement);
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_redact_line() {
+        let redactor = Redactor::new("salt");
+        let foo = redactor.nick_name("Foo");
+        let bar = redactor.nick_name("Bar");
+        let mut stats = RedactStats::default();
+        assert_eq!(foo, Redactor::new("salt").nick_name("Foo"));
+        assert_ne!(foo, Redactor::new("pepper").nick_name("Foo"));
+        assert_eq!(
+            redactor.redact_line(
+                "20:00:30.000| Damage. Victim: Foo, attacker: Bar, weapon 'Gun', damage: 10.0 DMG_DIRECT",
+                &mut stats
+            ),
+            format!(
+                "20:00:30.000| Damage. Victim: {}, attacker: {}, weapon 'Gun', damage: 10.0 DMG_DIRECT",
+                foo, bar
+            )
+        );
+        assert_eq!(
+            redactor.redact_line(
+                "20:00:05.000| player  1, uid 12, party 0, nickname: Foo, team: 1, bot: 0, ur: 6, mmHash: cd",
+                &mut stats
+            ),
+            format!(
+                "20:00:05.000| player  1, uid {}, party 0, nickname: {}, team: 1, bot: 0, ur: {}, mmHash: cd",
+                redactor.id("uid", 12),
+                foo,
+                redactor.id("ur", 6)
+            )
+        );
+        assert_eq!(stats, RedactStats::default());
+    }
+
+    #[test]
+    fn test_redact_kill_score_and_stripe_lines() {
+        let redactor = Redactor::new("salt");
+        let foo = redactor.nick_name("Foo");
+        let foo_bar = redactor.nick_name("Foo Bar");
+        let baz = redactor.nick_name("Baz");
+        let lines = [
+            (
+                "20:00:30.000| Kill. Victim: Foo Bar killer: Baz".to_string(),
+                format!("20:00:30.000| Kill. Victim: {} killer: {}", foo_bar, baz),
+            ),
+            (
+                "20:00:30.000| Score: player: 0, nick: Foo, Got: 15, reason: KILL".to_string(),
+                format!("20:00:30.000| Score: player: 0, nick: {}, Got: 15, reason: KILL", foo),
+            ),
+            (
+                "20:04:01.000| Stripe 'PvpWin' value increased by 1 for player 0 [Foo Bar].".to_string(),
+                format!("20:04:01.000| Stripe 'PvpWin' value increased by 1 for player 0 [{}].", foo_bar),
+            ),
+            (
+                "20:00:31.000| Damage. Victim: Baz, attacker: Foo Bar, weapon 'Gun', damage: 10.0 DMG_DIRECT"
+                    .to_string(),
+                format!(
+                    "20:00:31.000| Damage. Victim: {}, attacker: {}, weapon 'Gun', damage: 10.0 DMG_DIRECT",
+                    baz, foo_bar
+                ),
+            ),
+        ];
+        let mut stats = RedactStats::default();
+        for (line, redacted) in lines {
+            assert_eq!(redactor.redact_line(&line, &mut stats), redacted);
+        }
+        assert_eq!(stats.masked(), 0);
+    }
+
+    #[test]
+    fn test_mask_lines_not_redacted() {
+        let redactor = Redactor::new("salt");
+        let mut stats = RedactStats::default();
+        assert_eq!(redactor.redact_line("not a log line", &mut stats), MASK);
+        // the damage cannot be parsed, so the nicknames are not known
+        assert_eq!(
+            redactor.redact_line(
+                "20:00:30.000| Damage. Victim: Foo, attacker: Bar, weapon 'Gun', damage: lots",
+                &mut stats
+            ),
+            "20:00:30.000| <redacted>"
+        );
+        assert_eq!(stats.unparsed, 2);
+        // the stripe name contains the marker of the nickname
+        assert_eq!(
+            redactor.redact_line(
+                "20:04:01.000| Stripe 'Top [1]' value increased by 1 for player 0 [Foo].",
+                &mut stats
+            ),
+            "20:04:01.000| <redacted>"
+        );
+        assert_eq!(stats.unreplaced, 1);
+
+        let mut line = "nick: Foo".to_string();
+        assert!(!replace_field(&mut line, "nick: ", "Bar", "Baz"));
+        assert!(!replace_field(&mut line, "name: ", "Foo", "Baz"));
+        assert!(replace_field(&mut line, "nick: ", "Foo", "Baz"));
+        assert_eq!(line, "nick: Baz");
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+use crate::game::Round;
+use crate::log::{Payload, Spawn};
+
+/// Maps the nicknames and player numbers of the gameplay entries to the spawns of a round.
+///
+/// `Damage`, `Kill` and `Assist` lines only contain nicknames, `Score` and `Stripe` lines the player number. The roster lines of the round carry the `user_id`, the only identity surviving a nickname change.
+#[derive(Debug, Clone, Default)]
+pub struct PlayerResolver<'a> {
+    by_nick: HashMap<&'a str, Option<&'a Spawn>>,
+    by_no: HashMap<u8, &'a Spawn>,
+}
+
+impl<'a> PlayerResolver<'a> {
+    pub fn new(roster: &'a [Spawn]) -> Self {
+        let mut resolver = Self::default();
+        for spawn in roster {
+            resolver
+                .by_nick
+                .entry(spawn.nick_name.as_str())
+                .and_modify(|s| {
+                    // the nickname is ambiguous, e.g. bots of the same kind
+                    *s = None;
+                })
+                .or_insert(Some(spawn));
+            resolver.by_no.insert(spawn.player_no, spawn);
+        }
+        resolver
+    }
+
+    /// The spawn with the nickname, unless several spawns share it.
+    pub fn by_nick(&self, nick_name: &str) -> Option<&'a Spawn> {
+        self.by_nick.get(nick_name).copied().flatten()
+    }
+
+    pub fn by_no(&self, player_no: u8) -> Option<&'a Spawn> {
+        self.by_no.get(&player_no).copied()
+    }
+
+    /// The player acting in the entry: the attacker, killer, assistant or the player receiving a score or stripe.
+    pub fn actor(&self, message: &Payload) -> Option<&'a Spawn> {
+        match message {
+            Payload::Damage(damage) => self.by_nick(&damage.attacker),
+            Payload::Kill(kill) => self.by_nick(&kill.killer),
+            Payload::Assist(assist) => self.by_nick(&assist.assistant),
+            Payload::Score(score) => self.by_no(score.player_no),
+            Payload::Stripe(stripe) => self.by_no(stripe.player_no),
+            Payload::Player(player) => self.by_no(player.player_no),
+            Payload::Spawn(spawn) => self.by_no(spawn.player_no),
+            _ => None,
+        }
+    }
+
+    /// The player suffering the damage or kill.
+    pub fn victim(&self, message: &Payload) -> Option<&'a Spawn> {
+        match message {
+            Payload::Damage(damage) => self.by_nick(&damage.victim),
+            Payload::Kill(kill) => self.by_nick(&kill.victim),
+            _ => None,
+        }
+    }
+}
+
+impl Round {
+    pub fn resolver(&self) -> PlayerResolver<'_> {
+        PlayerResolver::new(&self.roster)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn spawn(player_no: u8, user_id: usize, nick_name: &str) -> Spawn {
+        Spawn {
+            player_no,
+            user_id,
+            party_id: 0,
+            nick_name: nick_name.to_string(),
+            team: 1,
+            bot: 0,
+            session: 0,
+            design_hash: 0,
+        }
+    }
+
+    #[test]
+    fn test_resolve_ambiguous_nick() {
+        let roster = vec![spawn(0, 11, "Foo"), spawn(1, 0, "Bot"), spawn(2, 0, "Bot")];
+        let resolver = PlayerResolver::new(&roster);
+        assert_eq!(resolver.by_nick("Foo").map(|s| s.user_id), Some(11));
+        assert_eq!(resolver.by_nick("Bot"), None);
+        assert_eq!(resolver.by_no(2).map(|s| s.player_no), Some(2));
+        assert_eq!(resolver.by_nick("Bar"), None);
+    }
+}
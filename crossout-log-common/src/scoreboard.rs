@@ -0,0 +1,195 @@
+use std::collections::BTreeMap;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::game::Round;
+use crate::log::{Payload, Spawn};
+
+/// The end of round scoreboard, computed from the entries of the round.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Scoreboard {
+    /// The players in order of the roster.
+    pub players: Vec<PlayerScore>,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlayerScore {
+    pub player_no: u8,
+    pub user_id: usize,
+    pub nick_name: String,
+    pub team: u8,
+    pub bot: bool,
+    /// Kills of other players. Destroying the own vehicle only counts as a death.
+    pub kills: usize,
+    pub deaths: usize,
+    pub assists: usize,
+    /// Damage dealt to opponents. Like the in-game scoreboard, damage to the own vehicle and to team mates is not counted.
+    pub damage_dealt: f32,
+    /// Damage received from other players.
+    pub damage_received: f32,
+    /// The damage dealt by `DamageFlag`. A hit usually has multiple flags, so the values do not add up to the damage dealt.
+    pub damage_dealt_by_flag: BTreeMap<String, f32>,
+    /// The damage received by `DamageFlag`.
+    pub damage_received_by_flag: BTreeMap<String, f32>,
+    pub score: f32,
+    /// The score by `ScoreReason`.
+    pub score_by_reason: BTreeMap<String, f32>,
+    /// The stripe values by stripe name.
+    pub stripes: BTreeMap<String, usize>,
+    /// The time between the round start and the first death, or the whole round if the player survived.
+    pub survival_sec: f32,
+}
+
+impl PlayerScore {
+    fn new(spawn: &Spawn) -> Self {
+        Self {
+            player_no: spawn.player_no,
+            user_id: spawn.user_id,
+            nick_name: spawn.nick_name.clone(),
+            team: spawn.team,
+            bot: spawn.bot != 0,
+            ..Self::default()
+        }
+    }
+}
+
+impl Scoreboard {
+    pub fn new(round: &Round) -> Self {
+        let mut players: Vec<PlayerScore> = round.roster.iter().map(PlayerScore::new).collect();
+        let resolver = round.resolver();
+        // the players are in order of the roster
+        let index = |spawn: Option<&Spawn>| {
+            spawn.and_then(|s| round.roster.iter().position(|r| r.player_no == s.player_no))
+        };
+        let round_sec = round.duration_sec().unwrap_or_else(|| {
+            round
+                .entries
+                .last()
+                .map(|e| (e.time_stamp - round.start).num_milliseconds() as f32 / 1000.0)
+                .unwrap_or_default()
+        });
+        let mut died = vec![None; players.len()];
+
+        for entry in round.entries.iter() {
+            let actor = index(resolver.actor(&entry.message));
+            let victim = index(resolver.victim(&entry.message));
+            match entry.message {
+                Payload::Damage(ref damage) => {
+                    if let (Some(a), Some(v)) = (actor, victim) {
+                        if a == v {
+                            continue;
+                        }
+                        let flags: Vec<String> =
+                            damage.flags.into_iter().map(|f| f.to_string()).collect();
+                        if players[a].team != players[v].team {
+                            let attacker = &mut players[a];
+                            attacker.damage_dealt += damage.value;
+                            for flag in flags.iter() {
+                                *attacker
+                                    .damage_dealt_by_flag
+                                    .entry(flag.clone())
+                                    .or_default() += damage.value;
+                            }
+                        }
+                        let victim = &mut players[v];
+                        victim.damage_received += damage.value;
+                        for flag in flags {
+                            *victim.damage_received_by_flag.entry(flag).or_default() +=
+                                damage.value;
+                        }
+                    }
+                }
+                Payload::Kill(_) => {
+                    if let Some(v) = victim {
+                        players[v].deaths += 1;
+                        died[v].get_or_insert(entry.time_stamp);
+                        if let Some(a) = actor.filter(|&a| a != v) {
+                            players[a].kills += 1;
+                        }
+                    }
+                }
+                Payload::Assist(_) => {
+                    if let Some(a) = actor {
+                        players[a].assists += 1;
+                    }
+                }
+                Payload::Score(ref score) => {
+                    if let Some(a) = actor {
+                        players[a].score += score.value;
+                        *players[a]
+                            .score_by_reason
+                            .entry(score.reason.to_string())
+                            .or_default() += score.value;
+                    }
+                }
+                Payload::Stripe(ref stripe) => {
+                    if let Some(a) = actor {
+                        *players[a].stripes.entry(stripe.name.clone()).or_default() += stripe.value;
+                    }
+                }
+                _ => {}
+            }
+        }
+        for (player, died) in players.iter_mut().zip(died) {
+            player.survival_sec = match died {
+                Some(died) => (died - round.start).num_milliseconds() as f32 / 1000.0,
+                None => round_sec,
+            };
+        }
+        Self { players }
+    }
+
+    pub fn player(&self, player_no: u8) -> Option<&PlayerScore> {
+        self.players.iter().find(|p| p.player_no == player_no)
+    }
+
+    /// The players of the team, highest score first.
+    pub fn team(&self, team: u8) -> Vec<&PlayerScore> {
+        let mut players: Vec<_> = self.players.iter().filter(|p| p.team == team).collect();
+        players.sort_by(|lhs, rhs| rhs.score.total_cmp(&lhs.score));
+        players
+    }
+}
+
+impl Round {
+    pub fn scoreboard(&self) -> Scoreboard {
+        Scoreboard::new(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fixture;
+
+    #[test]
+    fn test_scoreboard() {
+        let games = fixture::games(
+            "20:00:00.000| ====== starting level 3: 'levels/maps/bad_rock' pvp ======
+            20:00:05.000| ===== Gameplay 'Pvp' started, map 'bad_rock' ======
+            20:00:05.000| player  0, uid 11, party 1, nickname: Foo, team: 1, bot: 0, ur: 5, mmHash: ab
+            20:00:05.000| player  1, uid 12, party 2, nickname: Bar, team: 2, bot: 0, ur: 6, mmHash: cd
+            20:00:30.000| Damage. Victim: Bar, attacker: Foo, weapon 'Gun', damage: 10.5 DMG_DIRECT
+            20:00:30.500| Damage. Victim: Foo, attacker: Foo, weapon 'Gun', damage: 2.0 DMG_BLAST
+            20:00:31.000| Damage. Victim: Bar, attacker: Foo, weapon 'Gun', damage: 4.5 DMG_DIRECT|DMG_BLAST
+            20:00:31.000| Kill. Victim: Bar killer: Foo
+            20:00:32.000| Score: player: 0, nick: Foo, Got: 15, reason: KILL
+            20:02:00.000| ===== Gameplay finish, reason: no_cars, winner team 1, win reason: MORE_CARS_LEFT, battle time: 115.0 sec =====",
+        );
+        let board = games[0].rounds[0].scoreboard();
+        let foo = board.player(0).unwrap();
+        assert_eq!(foo.kills, 1);
+        assert_eq!(foo.damage_dealt, 15.0);
+        assert_eq!(foo.damage_dealt_by_flag["DMG_BLAST"], 4.5);
+        assert_eq!(foo.damage_received, 0.0);
+        assert_eq!(foo.score_by_reason["KILL"], 15.0);
+        assert_eq!(foo.survival_sec, 115.0);
+        let bar = board.player(1).unwrap();
+        assert_eq!(bar.deaths, 1);
+        assert_eq!(bar.damage_received, 15.0);
+        assert_eq!(bar.survival_sec, 26.0);
+        assert_eq!(board.team(1)[0].nick_name, "Foo");
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::BTreeMap;
+
+use chrono::{Duration, NaiveDateTime};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::game::Game;
+use crate::log::{Damage, Payload};
+
+/// The damage output of the weapons fired during a `TestDrive` session.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestDrive {
+    pub start: NaiveDateTime,
+    pub end: Option<NaiveDateTime>,
+    /// The weapons in order of the first shot.
+    pub weapons: Vec<WeaponBenchmark>,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeaponBenchmark {
+    pub weapon: String,
+    pub hits: usize,
+    pub damage: f32,
+    pub first_shot: NaiveDateTime,
+    pub last_shot: NaiveDateTime,
+    /// The damage per second between the first and the last shot, `None` if all hits were at the same time.
+    pub dps: Option<f32>,
+    /// The highest damage dealt within the burst window.
+    pub burst: f32,
+    /// The damage by `DamageFlag`. A hit usually has multiple flags, so the values do not add up to the damage.
+    pub damage_by_flag: BTreeMap<String, f32>,
+}
+
+impl WeaponBenchmark {
+    /// The time between the first and the last shot.
+    pub fn window_sec(&self) -> f32 {
+        (self.last_shot - self.first_shot).num_milliseconds() as f32 / 1000.0
+    }
+}
+
+impl TestDrive {
+    /// Benchmarks the damage dealt in the game, or returns `None` if the game is no test drive.
+    pub fn new(game: &Game, burst_window: Duration) -> Option<Self> {
+        if !game.test_drive {
+            return None;
+        }
+        let mut hits = BTreeMap::<&str, Vec<(NaiveDateTime, &Damage)>>::new();
+        let mut order = Vec::new();
+        for entry in game.rounds.iter().flat_map(|r| r.entries.iter()) {
+            if let Payload::Damage(ref damage) = entry.message {
+                if damage.attacker == damage.victim {
+                    continue;
+                }
+                let weapon = damage.weapon.as_str();
+                if !hits.contains_key(weapon) {
+                    order.push(weapon);
+                }
+                hits.entry(weapon)
+                    .or_default()
+                    .push((entry.time_stamp, damage));
+            }
+        }
+        let weapons = order
+            .into_iter()
+            .map(|weapon| benchmark(weapon, &hits[weapon], burst_window))
+            .collect();
+        Some(Self {
+            start: game.start,
+            end: game.end,
+            weapons,
+        })
+    }
+}
+
+fn benchmark(
+    weapon: &str,
+    hits: &[(NaiveDateTime, &Damage)],
+    burst_window: Duration,
+) -> WeaponBenchmark {
+    let first_shot = hits[0].0;
+    let last_shot = hits[hits.len() - 1].0;
+    let damage: f32 = hits.iter().map(|(_, d)| d.value).sum();
+    let mut damage_by_flag = BTreeMap::new();
+    for (_, hit) in hits {
+        for flag in hit.flags {
+            *damage_by_flag.entry(flag.to_string()).or_default() += hit.value;
+        }
+    }
+    // the largest sum of a window of hits, sliding over the hits in order of time
+    let mut burst = 0f32;
+    let mut window = 0f32;
+    let mut from = 0;
+    for &(time_stamp, hit) in hits {
+        window += hit.value;
+        while time_stamp - hits[from].0 > burst_window {
+            window -= hits[from].1.value;
+            from += 1;
+        }
+        burst = burst.max(window);
+    }
+    let window_sec = (last_shot - first_shot).num_milliseconds() as f32 / 1000.0;
+    WeaponBenchmark {
+        weapon: weapon.to_string(),
+        hits: hits.len(),
+        damage,
+        first_shot,
+        last_shot,
+        dps: (window_sec > 0.0).then(|| damage / window_sec),
+        burst,
+        damage_by_flag,
+    }
+}
+
+/// Benchmarks all test drives among the games.
+pub fn test_drives(games: &[Game], burst_window: Duration) -> Vec<TestDrive> {
+    games
+        .iter()
+        .filter_map(|g| TestDrive::new(g, burst_window))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixture;
+
+    #[test]
+    fn test_benchmark_test_drive() {
+        let games = fixture::games(
+            "20:00:00.000| ====== TestDrive started ======
+            20:00:10.000| Damage. Victim: Dummy, attacker: Foo, weapon 'Gun', damage: 10.0 DMG_DIRECT
+            20:00:10.500| Damage. Victim: Dummy, attacker: Foo, weapon 'Gun', damage: 10.0 DMG_DIRECT
+            20:00:12.000| Damage. Victim: Dummy, attacker: Foo, weapon 'Gun', damage: 10.0 DMG_DIRECT|DMG_BLAST
+            20:00:14.000| Damage. Victim: Dummy, attacker: Foo, weapon 'Gun', damage: 10.0 DMG_DIRECT
+            20:00:20.000| ====== TestDrive finish ======",
+        );
+        let drives = test_drives(&games, Duration::seconds(1));
+        assert_eq!(drives.len(), 1);
+        let gun = &drives[0].weapons[0];
+        assert_eq!(gun.hits, 4);
+        assert_eq!(gun.window_sec(), 4.0);
+        assert_eq!(gun.dps, Some(10.0));
+        assert_eq!(gun.burst, 20.0);
+        assert_eq!(gun.damage_by_flag["DMG_BLAST"], 10.0);
+    }
+
+    #[test]
+    fn test_benchmark_ignores_self_damage() {
+        let games = fixture::games(
+            "20:00:00.000| ====== TestDrive started ======
+            20:00:10.000| Damage. Victim: Foo, attacker: Foo, weapon 'Mortar', damage: 50.0 DMG_BLAST
+            20:00:11.000| Damage. Victim: Dummy, attacker: Foo, weapon 'Gun', damage: 10.0 DMG_DIRECT
+            20:00:12.000| Damage. Victim: Foo, attacker: Foo, weapon 'Gun', damage: 5.0 DMG_DIRECT
+            20:00:20.000| ====== TestDrive finish ======",
+        );
+        let drives = test_drives(&games, Duration::seconds(1));
+        assert_eq!(drives[0].weapons.len(), 1);
+        let gun = &drives[0].weapons[0];
+        assert_eq!(gun.weapon, "Gun");
+        assert_eq!(gun.hits, 1);
+        assert_eq!(gun.damage, 10.0);
+        assert_eq!(gun.dps, None);
+    }
+}
@@ -0,0 +1 @@
+ALTER TABLE rounds ALTER COLUMN winning_team TYPE REAL;
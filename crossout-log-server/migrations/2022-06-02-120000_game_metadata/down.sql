@@ -0,0 +1,8 @@
+DROP INDEX games_start_ts_idx;
+ALTER TABLE games
+    DROP COLUMN game_mode_id,
+    DROP COLUMN level_no,
+    DROP COLUMN end_ts,
+    DROP COLUMN duration,
+    DROP COLUMN test_drive;
+DROP TABLE game_modes;
@@ -0,0 +1,11 @@
+CREATE TABLE game_modes (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(64) NOT NULL UNIQUE
+);
+ALTER TABLE games
+    ADD COLUMN game_mode_id INTEGER REFERENCES game_modes(id),
+    ADD COLUMN level_no SMALLINT NOT NULL DEFAULT 0,
+    ADD COLUMN end_ts TIMESTAMPTZ,
+    ADD COLUMN duration REAL,
+    ADD COLUMN test_drive BOOLEAN NOT NULL DEFAULT FALSE;
+CREATE INDEX games_start_ts_idx ON games(start_ts);
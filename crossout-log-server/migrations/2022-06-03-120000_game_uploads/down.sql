@@ -0,0 +1,2 @@
+ALTER TABLE kills DROP COLUMN kill_ts;
+DROP TABLE game_uploads;
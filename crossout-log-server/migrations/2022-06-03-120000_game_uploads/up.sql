@@ -0,0 +1,8 @@
+CREATE TABLE game_uploads (
+    id SERIAL PRIMARY KEY,
+    game_id INTEGER NOT NULL REFERENCES games(id),
+    uploader BIGINT NOT NULL,
+    upload_ts TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (game_id, uploader)
+);
+ALTER TABLE kills ADD COLUMN kill_ts TIMESTAMPTZ;
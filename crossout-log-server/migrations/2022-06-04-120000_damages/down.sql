@@ -0,0 +1,2 @@
+DROP INDEX spawns_round_id_idx, kills_victim_id_idx, kills_killer_id_idx;
+DROP TABLE damages;
@@ -0,0 +1,15 @@
+CREATE TABLE damages (
+    id SERIAL PRIMARY KEY,
+    round_id INTEGER NOT NULL REFERENCES rounds(id),
+    attacker_id INTEGER NOT NULL REFERENCES spawns(id),
+    victim_id INTEGER NOT NULL REFERENCES spawns(id),
+    weapon_id INTEGER NOT NULL REFERENCES weapons(id),
+    value REAL NOT NULL,
+    flags INTEGER NOT NULL,
+    damage_ts TIMESTAMPTZ NOT NULL
+);
+CREATE INDEX damages_round_id_idx ON damages(round_id);
+CREATE INDEX damages_attacker_id_idx ON damages(attacker_id);
+CREATE INDEX kills_killer_id_idx ON kills(killer_id);
+CREATE INDEX kills_victim_id_idx ON kills(victim_id);
+CREATE INDEX spawns_round_id_idx ON spawns(round_id);
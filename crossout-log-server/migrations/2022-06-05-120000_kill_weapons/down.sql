@@ -0,0 +1,2 @@
+DROP INDEX damages_weapon_id_idx;
+ALTER TABLE kills DROP COLUMN weapon_id;
@@ -0,0 +1,2 @@
+ALTER TABLE kills ADD COLUMN weapon_id INTEGER REFERENCES weapons(id);
+CREATE INDEX damages_weapon_id_idx ON damages(weapon_id);
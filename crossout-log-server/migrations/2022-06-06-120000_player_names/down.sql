@@ -0,0 +1,4 @@
+-- the merged players are not split again
+DROP INDEX spawns_player_id_idx;
+DROP INDEX players_user_id_key;
+DROP TABLE player_names;
@@ -0,0 +1,44 @@
+CREATE TABLE player_names (
+    id SERIAL PRIMARY KEY,
+    player_id INTEGER NOT NULL REFERENCES players(id),
+    name VARCHAR NOT NULL,
+    first_seen TIMESTAMPTZ NOT NULL,
+    last_seen TIMESTAMPTZ NOT NULL,
+    UNIQUE(player_id, name)
+);
+
+-- players with a user id are identified by it alone, bots by their name
+CREATE TEMPORARY TABLE player_merges AS
+SELECT p.id, min(p.id) OVER (PARTITION BY p.user_id) AS merged_id
+FROM players p
+WHERE p.user_id <> 0;
+
+INSERT INTO player_names (player_id, name, first_seen, last_seen)
+SELECT coalesce(pm.merged_id, p.id), p.name, min(g.start_ts), max(g.start_ts)
+FROM players p
+LEFT JOIN player_merges pm ON pm.id = p.id
+JOIN spawns s ON s.player_id = p.id
+JOIN rounds r ON r.id = s.round_id
+JOIN games g ON g.id = r.game_id
+GROUP BY coalesce(pm.merged_id, p.id), p.name;
+
+UPDATE spawns s SET player_id = pm.merged_id
+FROM player_merges pm
+WHERE pm.id = s.player_id AND pm.id <> pm.merged_id;
+
+DELETE FROM players p
+USING player_merges pm
+WHERE pm.id = p.id AND pm.id <> pm.merged_id;
+
+UPDATE players p SET name = (
+    SELECT pn.name FROM player_names pn
+    WHERE pn.player_id = p.id
+    ORDER BY pn.last_seen DESC
+    LIMIT 1
+)
+WHERE EXISTS (SELECT 1 FROM player_names pn WHERE pn.player_id = p.id);
+
+DROP TABLE player_merges;
+
+CREATE UNIQUE INDEX players_user_id_key ON players(user_id) WHERE user_id <> 0;
+CREATE INDEX spawns_player_id_idx ON spawns(player_id);
@@ -0,0 +1,17 @@
+CREATE TABLE ratings (
+    id SERIAL PRIMARY KEY,
+    player_id INTEGER NOT NULL UNIQUE REFERENCES players(id),
+    rating DOUBLE PRECISION NOT NULL,
+    rounds INTEGER NOT NULL,
+    updated_ts TIMESTAMPTZ NOT NULL
+);
+CREATE TABLE rating_history (
+    id SERIAL PRIMARY KEY,
+    player_id INTEGER NOT NULL REFERENCES players(id),
+    round_id INTEGER NOT NULL REFERENCES rounds(id),
+    rating DOUBLE PRECISION NOT NULL,
+    delta DOUBLE PRECISION NOT NULL,
+    rating_ts TIMESTAMPTZ NOT NULL,
+    UNIQUE (player_id, round_id)
+);
+CREATE INDEX rating_history_round_id_idx ON rating_history(round_id);
@@ -0,0 +1,2 @@
+DROP TABLE highlights;
+DROP TYPE highlight_kind;
@@ -0,0 +1,17 @@
+CREATE TYPE highlight_kind AS ENUM (
+    'first_blood',
+    'multi_kill',
+    'streak',
+    'trade'
+);
+CREATE TABLE highlights (
+    id SERIAL PRIMARY KEY,
+    round_id INTEGER NOT NULL REFERENCES rounds(id),
+    spawn_id INTEGER NOT NULL REFERENCES spawns(id),
+    kind highlight_kind NOT NULL,
+    kills SMALLINT NOT NULL,
+    other_id INTEGER REFERENCES spawns(id),
+    highlight_ts TIMESTAMPTZ NOT NULL
+);
+CREATE INDEX highlights_round_id_idx ON highlights(round_id);
+CREATE INDEX highlights_spawn_id_idx ON highlights(spawn_id);
@@ -0,0 +1,2 @@
+ALTER TABLE rounds DROP COLUMN side_swapped;
+ALTER TABLE games DROP COLUMN series_winner;
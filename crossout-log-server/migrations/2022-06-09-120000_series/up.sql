@@ -0,0 +1,2 @@
+ALTER TABLE games ADD COLUMN series_winner SMALLINT;
+ALTER TABLE rounds ADD COLUMN side_swapped BOOLEAN NOT NULL DEFAULT FALSE;
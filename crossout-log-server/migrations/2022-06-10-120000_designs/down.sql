@@ -0,0 +1,2 @@
+DROP INDEX spawns_design_idx;
+DROP TABLE designs;
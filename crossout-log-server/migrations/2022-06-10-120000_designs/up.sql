@@ -0,0 +1,27 @@
+CREATE TABLE designs (
+    id SERIAL PRIMARY KEY,
+    hash BIGINT NOT NULL UNIQUE,
+    owner_id INTEGER REFERENCES players(id),
+    label VARCHAR,
+    first_seen TIMESTAMPTZ NOT NULL,
+    last_seen TIMESTAMPTZ NOT NULL
+);
+CREATE INDEX designs_owner_id_idx ON designs(owner_id);
+CREATE INDEX spawns_design_idx ON spawns(design);
+
+INSERT INTO designs (hash, first_seen, last_seen)
+SELECT s.design, min(g.start_ts), max(g.start_ts)
+FROM spawns s
+JOIN rounds r ON r.id = s.round_id
+JOIN games g ON g.id = r.game_id
+WHERE s.bot = 0
+GROUP BY s.design;
+
+-- the owner is the human player who spawned the design most often
+UPDATE designs d SET owner_id = (
+    SELECT s.player_id FROM spawns s
+    WHERE s.design = d.hash AND s.bot = 0
+    GROUP BY s.player_id
+    ORDER BY count(*) DESC, min(s.id)
+    LIMIT 1
+);
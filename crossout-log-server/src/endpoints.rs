@@ -1,17 +1,49 @@
 use std::env;
 
 use actix_web::{
-    middleware, web,
-    web::{Data, Json},
+    error, middleware, web,
+    web::{Bytes, Data, Json, Path, Query},
     App, Error as ActixError, HttpResponse, HttpServer,
 };
 use diesel::backend::Backend;
 use diesel::r2d2::{ConnectionManager, Pool};
 use juniper::http::playground::playground_source;
+use serde::Deserialize;
 use std::sync::Arc;
 
+use crossout_log_common::filter::Filter;
+use crossout_log_common::game::assemble_games;
+use crossout_log_common::log::Entry;
+use crossout_log_common::owner::infer_owner;
+
 use crate::generated::*;
 use crate::db::*;
+use crate::ingest::ingest_games;
+use crate::rating::{rating_changes, recompute_ratings, top_ratings};
+use crate::stats::clanwars::clan_wars;
+use crate::stats::designs::{design_stats, label_design};
+use crate::stats::export::export_entries;
+use crate::stats::leaderboard::{leaderboard, LeaderboardFilter, Metric};
+use crate::stats::maps::map_stats;
+use crate::stats::parties::party_stats;
+use crate::stats::players::player_profile;
+use crate::stats::weapons::weapon_stats;
+use crate::stats::GameFilter;
+
+/// The maximum size of an uploaded object file.
+const UPLOAD_LIMIT: usize = 256 * 1024 * 1024;
+/// The default number of recent games in a player profile.
+const RECENT_GAMES: i64 = 20;
+/// The maximum number of recent games in a player profile.
+const MAX_RECENT_GAMES: i64 = 100;
+/// The default number of series in the clan war history.
+const CLAN_WAR_LIMIT: i64 = 50;
+/// The default number of players in the rating list.
+const RATING_LIMIT: i64 = 100;
+/// The default number of designs listed.
+const DESIGN_LIMIT: i64 = 100;
+/// The default number of exported entries.
+const EXPORT_LIMIT: usize = 10_000;
 
 
 async fn graphql_playground() -> HttpResponse {
@@ -31,13 +63,274 @@ async fn graphql(
         .body(serde_json::to_string(&res)?))
 }
 
-async fn upload_logs() -> Result<HttpResponse, ActixError> {
-    todo!()
+#[derive(Deserialize)]
+pub struct UploadQuery {
+    /// The user id of the player who wrote the logs, inferred from the logs if omitted.
+    uploader: Option<i64>,
+}
+
+/// Stores the entries of a bincode object file, as written by the crossout-log-watcher.
+async fn upload_logs(
+    query: Query<UploadQuery>,
+    body: Bytes,
+    st: Data<AppState>,
+) -> Result<HttpResponse, ActixError> {
+    let entries: Vec<Entry> = bincode::deserialize(&body).map_err(error::ErrorBadRequest)?;
+    let games = assemble_games(entries);
+    let uploader = infer_owner(&games, query.uploader.map(|u| u as usize))
+        .ok_or_else(|| error::ErrorBadRequest("The uploader could not be inferred from the logs"))?;
+    let conn = st.get_ref().pool.get().expect("Fail to get pool");
+    let games = ingest_games(&conn, uploader as i64, &games)
+        .map_err(error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(games))
+}
+
+async fn get_leaderboard(
+    metric: Path<Metric>,
+    filter: Query<LeaderboardFilter>,
+    st: Data<AppState>,
+) -> Result<HttpResponse, ActixError> {
+    let conn = st.get_ref().pool.get().expect("Fail to get pool");
+    let rows = leaderboard(&conn, *metric, &filter).map_err(error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(rows))
+}
+
+async fn get_weapons(
+    filter: Query<GameFilter>,
+    st: Data<AppState>,
+) -> Result<HttpResponse, ActixError> {
+    let conn = st.get_ref().pool.get().expect("Fail to get pool");
+    let weapons = weapon_stats(&conn, None, &filter).map_err(error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(weapons))
+}
+
+async fn get_weapon(
+    name: Path<String>,
+    filter: Query<GameFilter>,
+    st: Data<AppState>,
+) -> Result<HttpResponse, ActixError> {
+    let conn = st.get_ref().pool.get().expect("Fail to get pool");
+    let weapon = weapon_stats(&conn, Some(&name), &filter)
+        .map_err(error::ErrorInternalServerError)?
+        .pop()
+        .ok_or_else(|| error::ErrorNotFound(format!("No damage dealt with `{}`", name)))?;
+    Ok(HttpResponse::Ok().json(weapon))
+}
+
+async fn get_maps(
+    filter: Query<GameFilter>,
+    st: Data<AppState>,
+) -> Result<HttpResponse, ActixError> {
+    let conn = st.get_ref().pool.get().expect("Fail to get pool");
+    let maps = map_stats(&conn, None, &filter).map_err(error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(maps))
+}
+
+async fn get_map(
+    id: Path<i32>,
+    filter: Query<GameFilter>,
+    st: Data<AppState>,
+) -> Result<HttpResponse, ActixError> {
+    let conn = st.get_ref().pool.get().expect("Fail to get pool");
+    let map = map_stats(&conn, Some(*id), &filter)
+        .map_err(error::ErrorInternalServerError)?
+        .pop()
+        .ok_or_else(|| error::ErrorNotFound(format!("No rounds played on map {}", id)))?;
+    Ok(HttpResponse::Ok().json(map))
+}
+
+#[derive(Deserialize)]
+pub struct ProfileQuery {
+    /// The number of recent games to list.
+    recent: Option<i64>,
+}
+
+async fn get_player(
+    user_id: Path<i64>,
+    query: Query<ProfileQuery>,
+    filter: Query<GameFilter>,
+    st: Data<AppState>,
+) -> Result<HttpResponse, ActixError> {
+    let conn = st.get_ref().pool.get().expect("Fail to get pool");
+    let recent = query.recent.unwrap_or(RECENT_GAMES).clamp(0, MAX_RECENT_GAMES);
+    let profile = player_profile(&conn, *user_id, recent, &filter)
+        .map_err(error::ErrorInternalServerError)?
+        .ok_or_else(|| error::ErrorNotFound(format!("No player with user id {}", user_id)))?;
+    Ok(HttpResponse::Ok().json(profile))
+}
+
+#[derive(Deserialize)]
+pub struct RatingQuery {
+    limit: Option<i64>,
+}
+
+async fn get_ratings(
+    query: Query<RatingQuery>,
+    st: Data<AppState>,
+) -> Result<HttpResponse, ActixError> {
+    let conn = st.get_ref().pool.get().expect("Fail to get pool");
+    let limit = query.limit.unwrap_or(RATING_LIMIT).clamp(1, 1000);
+    let ratings = top_ratings(&conn, limit).map_err(error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(ratings))
+}
+
+async fn get_rating_history(
+    user_id: Path<i64>,
+    st: Data<AppState>,
+) -> Result<HttpResponse, ActixError> {
+    let conn = st.get_ref().pool.get().expect("Fail to get pool");
+    let changes = rating_changes(&conn, *user_id).map_err(error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(changes))
+}
+
+/// Rates all rounds again, returns the number of rated rounds.
+async fn post_recompute_ratings(st: Data<AppState>) -> Result<HttpResponse, ActixError> {
+    let conn = st.get_ref().pool.get().expect("Fail to get pool");
+    let rated = recompute_ratings(&conn).map_err(error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(rated))
+}
+
+#[derive(Deserialize)]
+pub struct ClanWarQuery {
+    limit: Option<i64>,
+}
+
+async fn get_clan_wars(
+    query: Query<ClanWarQuery>,
+    filter: Query<GameFilter>,
+    st: Data<AppState>,
+) -> Result<HttpResponse, ActixError> {
+    let conn = st.get_ref().pool.get().expect("Fail to get pool");
+    let limit = query.limit.unwrap_or(CLAN_WAR_LIMIT).clamp(1, 1000);
+    let series = clan_wars(&conn, limit, &filter).map_err(error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(series))
+}
+
+#[derive(Deserialize)]
+pub struct PartyQuery {
+    /// Only the parties of the player with the user id.
+    user_id: Option<i64>,
+}
+
+async fn get_parties(
+    query: Query<PartyQuery>,
+    filter: Query<GameFilter>,
+    st: Data<AppState>,
+) -> Result<HttpResponse, ActixError> {
+    let conn = st.get_ref().pool.get().expect("Fail to get pool");
+    let parties =
+        party_stats(&conn, query.user_id, &filter).map_err(error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(parties))
+}
+
+#[derive(Deserialize)]
+pub struct DesignQuery {
+    /// Only the designs owned by the player with the user id.
+    user_id: Option<i64>,
+    limit: Option<i64>,
+}
+
+async fn get_designs(
+    query: Query<DesignQuery>,
+    filter: Query<GameFilter>,
+    st: Data<AppState>,
+) -> Result<HttpResponse, ActixError> {
+    let conn = st.get_ref().pool.get().expect("Fail to get pool");
+    let limit = query.limit.unwrap_or(DESIGN_LIMIT).clamp(1, 1000);
+    let designs = design_stats(&conn, None, query.user_id, limit, &filter)
+        .map_err(error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(designs))
+}
+
+async fn get_design(
+    hash: Path<i64>,
+    filter: Query<GameFilter>,
+    st: Data<AppState>,
+) -> Result<HttpResponse, ActixError> {
+    let conn = st.get_ref().pool.get().expect("Fail to get pool");
+    let design = design_stats(&conn, Some(*hash), None, 1, &filter)
+        .map_err(error::ErrorInternalServerError)?
+        .pop()
+        .ok_or_else(|| error::ErrorNotFound(format!("No rounds played with design {}", hash)))?;
+    Ok(HttpResponse::Ok().json(design))
+}
+
+#[derive(Deserialize)]
+pub struct DesignLabel {
+    /// The name of the design, `null` removes the name.
+    label: Option<String>,
+}
+
+async fn put_design_label(
+    hash: Path<i64>,
+    Json(body): Json<DesignLabel>,
+    st: Data<AppState>,
+) -> Result<HttpResponse, ActixError> {
+    let conn = st.get_ref().pool.get().expect("Fail to get pool");
+    let label = body.label.as_deref().map(str::trim).filter(|l| !l.is_empty());
+    if !label_design(&conn, *hash, label).map_err(error::ErrorInternalServerError)? {
+        return Err(error::ErrorNotFound(format!("No design {}", hash)));
+    }
+    Ok(HttpResponse::Ok().json(label))
+}
+
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    /// Only the entries of the game with the id.
+    game_id: Option<i32>,
+    /// A filter expression, such as `type == Damage && attacker == "Foo" && flags contains BLAST`. Requires a game id, or a `from` and `to` time window.
+    #[serde(rename = "where")]
+    where_: Option<String>,
+    limit: Option<usize>,
+}
+
+/// Exports the damage, kill and assist entries of the games as JSON.
+async fn get_export(
+    query: Query<ExportQuery>,
+    filter: Query<GameFilter>,
+    st: Data<AppState>,
+) -> Result<HttpResponse, ActixError> {
+    let where_ = query
+        .where_
+        .as_deref()
+        .map(str::parse::<Filter>)
+        .transpose()
+        .map_err(error::ErrorBadRequest)?;
+    let bounded = query.game_id.is_some() || (filter.from.is_some() && filter.to.is_some());
+    if where_.is_some() && !bounded {
+        return Err(error::ErrorBadRequest(
+            "A where filter requires a game_id, or a from and to time window",
+        ));
+    }
+    let conn = st.get_ref().pool.get().expect("Fail to get pool");
+    let limit = query.limit.unwrap_or(EXPORT_LIMIT).clamp(1, 100_000);
+    let entries = export_entries(&conn, query.game_id, where_.as_ref(), limit, &filter)
+        .map_err(error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(entries))
 }
 
 pub fn configure_endpoints(cfg: &mut web::ServiceConfig) {
     cfg.route("/graphql", web::get().to(graphql_playground))
         .route("/graphql", web::post().to(graphql));
+    cfg.service(web::resource("/api/upload")
+        .app_data(web::PayloadConfig::new(UPLOAD_LIMIT))
+        .route(web::post().to(upload_logs))
+    );
+    cfg.route("/api/export", web::get().to(get_export))
+        .route("/api/leaderboards/{metric}", web::get().to(get_leaderboard))
+        .route("/api/weapons", web::get().to(get_weapons))
+        .route("/api/weapons/{name}", web::get().to(get_weapon))
+        .route("/api/maps", web::get().to(get_maps))
+        .route("/api/maps/{id}", web::get().to(get_map))
+        .route("/api/designs", web::get().to(get_designs))
+        .route("/api/designs/{hash}", web::get().to(get_design))
+        .route("/api/designs/{hash}/label", web::put().to(put_design_label))
+        .route("/api/parties", web::get().to(get_parties))
+        .route("/api/players/{user_id}", web::get().to(get_player))
+        .route("/api/clanwars", web::get().to(get_clan_wars))
+        .route("/api/ratings", web::get().to(get_ratings))
+        .route("/api/ratings/recompute", web::post().to(post_recompute_ratings))
+        .route("/api/ratings/{user_id}", web::get().to(get_rating_history));
     cfg.service(web::resource("/test")
         .route(web::get().to(|| HttpResponse::Ok()))
         .route(web::head().to(|| HttpResponse::MethodNotAllowed()))
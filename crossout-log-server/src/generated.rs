@@ -2,6 +2,9 @@ use wundergraph::query_builder::types::{HasMany, HasOne};
 use wundergraph::scalar::WundergraphScalarValue;
 use wundergraph::WundergraphEntity;
 
+use crossout_log_common::highlights::HighlightKind;
+use crossout_log_common::log::{FinishReason, ScoreReason, WinReason};
+
 use crate::schema::*;
 
 #[derive(Clone, Debug, Identifiable, WundergraphEntity)]
@@ -26,6 +29,50 @@ pub struct Badge {
     stripes: HasMany<Stripe, stripes::badge_id>,
 }
 
+#[derive(Clone, Debug, Identifiable, WundergraphEntity)]
+#[table_name = "damages"]
+#[primary_key(id)]
+pub struct Damage {
+    id: i32,
+    round_id: HasOne<i32, Round>,
+    attacker_id: i32,
+    victim_id: i32,
+    weapon_id: HasOne<i32, Weapon>,
+    value: f32,
+    flags: i32,
+    damage_ts: chrono::DateTime<chrono::offset::Utc>,
+}
+
+#[derive(Clone, Debug, Identifiable, WundergraphEntity)]
+#[table_name = "designs"]
+#[primary_key(id)]
+pub struct Design {
+    id: i32,
+    hash: i64,
+    owner_id: Option<i32>,
+    label: Option<String>,
+    first_seen: chrono::DateTime<chrono::offset::Utc>,
+    last_seen: chrono::DateTime<chrono::offset::Utc>,
+}
+
+#[derive(Clone, Debug, Identifiable, WundergraphEntity)]
+#[table_name = "game_modes"]
+#[primary_key(id)]
+pub struct GameMode {
+    id: i32,
+    name: String,
+}
+
+#[derive(Clone, Debug, Identifiable, WundergraphEntity)]
+#[table_name = "game_uploads"]
+#[primary_key(id)]
+pub struct GameUpload {
+    id: i32,
+    game_id: HasOne<i32, Game>,
+    uploader: i64,
+    upload_ts: chrono::DateTime<chrono::offset::Utc>,
+}
+
 #[derive(Clone, Debug, Identifiable, WundergraphEntity)]
 #[table_name = "games"]
 #[primary_key(id)]
@@ -33,9 +80,29 @@ pub struct Game {
     id: i32,
     map_id: HasOne<i32, Map>,
     start_ts: chrono::DateTime<chrono::offset::Utc>,
+    game_mode_id: Option<i32>,
+    level_no: i16,
+    end_ts: Option<chrono::DateTime<chrono::offset::Utc>>,
+    duration: Option<f32>,
+    test_drive: bool,
+    series_winner: Option<i16>,
+    game_uploads: HasMany<GameUpload, game_uploads::game_id>,
     rounds: HasMany<Round, rounds::game_id>,
 }
 
+#[derive(Clone, Debug, Identifiable, WundergraphEntity)]
+#[table_name = "highlights"]
+#[primary_key(id)]
+pub struct Highlight {
+    id: i32,
+    round_id: HasOne<i32, Round>,
+    spawn_id: i32,
+    kind: HighlightKind,
+    kills: i16,
+    other_id: Option<i32>,
+    highlight_ts: chrono::DateTime<chrono::offset::Utc>,
+}
+
 #[derive(Clone, Debug, Identifiable, WundergraphEntity)]
 #[table_name = "kills"]
 #[primary_key(id)]
@@ -44,6 +111,8 @@ pub struct Kill {
     round_id: HasOne<i32, Round>,
     killer_id: i32,
     victim_id: i32,
+    kill_ts: Option<chrono::DateTime<chrono::offset::Utc>>,
+    weapon_id: Option<i32>,
     assists: HasMany<Assist, assists::kill_id>,
 }
 
@@ -56,6 +125,17 @@ pub struct Map {
     games: HasMany<Game, games::map_id>,
 }
 
+#[derive(Clone, Debug, Identifiable, WundergraphEntity)]
+#[table_name = "player_names"]
+#[primary_key(id)]
+pub struct PlayerName {
+    id: i32,
+    player_id: HasOne<i32, Player>,
+    name: String,
+    first_seen: chrono::DateTime<chrono::offset::Utc>,
+    last_seen: chrono::DateTime<chrono::offset::Utc>,
+}
+
 #[derive(Clone, Debug, Identifiable, WundergraphEntity)]
 #[table_name = "players"]
 #[primary_key(id)]
@@ -63,9 +143,35 @@ pub struct Player {
     id: i32,
     user_id: i64,
     name: String,
+    player_names: HasMany<PlayerName, player_names::player_id>,
+    rating_history: HasMany<RatingHistory, rating_history::player_id>,
+    ratings: HasMany<Rating, ratings::player_id>,
     spawns: HasMany<Spawn, spawns::player_id>,
 }
 
+#[derive(Clone, Debug, Identifiable, WundergraphEntity)]
+#[table_name = "rating_history"]
+#[primary_key(id)]
+pub struct RatingHistory {
+    id: i32,
+    player_id: HasOne<i32, Player>,
+    round_id: HasOne<i32, Round>,
+    rating: f64,
+    delta: f64,
+    rating_ts: chrono::DateTime<chrono::offset::Utc>,
+}
+
+#[derive(Clone, Debug, Identifiable, WundergraphEntity)]
+#[table_name = "ratings"]
+#[primary_key(id)]
+pub struct Rating {
+    id: i32,
+    player_id: HasOne<i32, Player>,
+    rating: f64,
+    rounds: i32,
+    updated_ts: chrono::DateTime<chrono::offset::Utc>,
+}
+
 #[derive(Clone, Debug, Identifiable, WundergraphEntity)]
 #[table_name = "rounds"]
 #[primary_key(id)]
@@ -75,10 +181,14 @@ pub struct Round {
     start_ts: chrono::DateTime<chrono::offset::Utc>,
     round_no: i16,
     duration: f32,
-    finish_reason: i16,
-    win_reason: i16,
-    winning_team: f32,
+    finish_reason: FinishReason,
+    win_reason: WinReason,
+    winning_team: i16,
+    side_swapped: bool,
+    damages: HasMany<Damage, damages::round_id>,
+    highlights: HasMany<Highlight, highlights::round_id>,
     kills: HasMany<Kill, kills::round_id>,
+    rating_history: HasMany<RatingHistory, rating_history::round_id>,
     spawns: HasMany<Spawn, spawns::round_id>,
 }
 
@@ -89,7 +199,7 @@ pub struct Score {
     id: i32,
     spawn_id: HasOne<i32, Spawn>,
     value: f32,
-    reason: i16,
+    reason: ScoreReason,
 }
 
 #[derive(Clone, Debug, Identifiable, WundergraphEntity)]
@@ -128,6 +238,7 @@ pub struct Weapon {
     id: i32,
     name: String,
     assists: HasMany<Assist, assists::weapon_id>,
+    damages: HasMany<Damage, damages::weapon_id>,
 }
 
 
@@ -136,10 +247,18 @@ wundergraph::query_object!{
     Query {
         Assist,
         Badge,
+        Damage,
+        Design,
+        GameMode,
+        GameUpload,
         Game,
+        Highlight,
         Kill,
         Map,
+        PlayerName,
         Player,
+        RatingHistory,
+        Rating,
         Round,
         Score,
         Spawn,
@@ -188,11 +307,32 @@ pub struct BadgeChangeset {
     name: String,
 }
 
+#[derive(Insertable, juniper::GraphQLInputObject, Clone, Debug)]
+#[graphql(scalar = "WundergraphScalarValue")]
+#[table_name = "game_modes"]
+pub struct NewGameMode {
+    name: String,
+}
+
+#[derive(AsChangeset, Identifiable, juniper::GraphQLInputObject, Clone, Debug)]
+#[graphql(scalar = "WundergraphScalarValue")]
+#[table_name = "game_modes"]
+#[primary_key(id)]
+pub struct GameModeChangeset {
+    id: i32,
+    name: String,
+}
+
 #[derive(Insertable, juniper::GraphQLInputObject, Clone, Debug)]
 #[graphql(scalar = "WundergraphScalarValue")]
 #[table_name = "games"]
 pub struct NewGame {
     start_ts: chrono::DateTime<chrono::offset::Utc>,
+    level_no: i16,
+    end_ts: Option<chrono::DateTime<chrono::offset::Utc>>,
+    duration: Option<f32>,
+    test_drive: bool,
+    series_winner: Option<i16>,
 }
 
 #[derive(AsChangeset, Identifiable, juniper::GraphQLInputObject, Clone, Debug)]
@@ -203,6 +343,12 @@ pub struct GameChangeset {
     id: i32,
     map_id: i32,
     start_ts: chrono::DateTime<chrono::offset::Utc>,
+    game_mode_id: Option<i32>,
+    level_no: i16,
+    end_ts: Option<chrono::DateTime<chrono::offset::Utc>>,
+    duration: Option<f32>,
+    test_drive: bool,
+    series_winner: Option<i16>,
 }
 
 #[derive(Insertable, juniper::GraphQLInputObject, Clone, Debug)]
@@ -213,6 +359,8 @@ pub struct NewKill {
     round_id: i32,
     killer_id: i32,
     victim_id: i32,
+    kill_ts: Option<chrono::DateTime<chrono::offset::Utc>>,
+    weapon_id: Option<i32>,
 }
 
 #[derive(AsChangeset, Identifiable, juniper::GraphQLInputObject, Clone, Debug)]
@@ -224,6 +372,8 @@ pub struct KillChangeset {
     round_id: i32,
     killer_id: i32,
     victim_id: i32,
+    kill_ts: Option<chrono::DateTime<chrono::offset::Utc>>,
+    weapon_id: Option<i32>,
 }
 
 #[derive(Insertable, juniper::GraphQLInputObject, Clone, Debug)]
@@ -267,9 +417,10 @@ pub struct NewRound {
     start_ts: chrono::DateTime<chrono::offset::Utc>,
     round_no: i16,
     duration: f32,
-    finish_reason: i16,
-    win_reason: i16,
-    winning_team: f32,
+    finish_reason: FinishReason,
+    win_reason: WinReason,
+    winning_team: i16,
+    side_swapped: bool,
 }
 
 #[derive(AsChangeset, Identifiable, juniper::GraphQLInputObject, Clone, Debug)]
@@ -282,9 +433,10 @@ pub struct RoundChangeset {
     start_ts: chrono::DateTime<chrono::offset::Utc>,
     round_no: i16,
     duration: f32,
-    finish_reason: i16,
-    win_reason: i16,
-    winning_team: f32,
+    finish_reason: FinishReason,
+    win_reason: WinReason,
+    winning_team: i16,
+    side_swapped: bool,
 }
 
 #[derive(Insertable, juniper::GraphQLInputObject, Clone, Debug)]
@@ -292,7 +444,7 @@ pub struct RoundChangeset {
 #[table_name = "scores"]
 pub struct NewScore {
     value: f32,
-    reason: i16,
+    reason: ScoreReason,
 }
 
 #[derive(AsChangeset, Identifiable, juniper::GraphQLInputObject, Clone, Debug)]
@@ -303,7 +455,7 @@ pub struct ScoreChangeset {
     id: i32,
     spawn_id: i32,
     value: f32,
-    reason: i16,
+    reason: ScoreReason,
 }
 
 #[derive(Insertable, juniper::GraphQLInputObject, Clone, Debug)]
@@ -374,6 +526,7 @@ wundergraph::mutation_object!{
     Mutation{
         Assist(insert = NewAssist, update = AssistChangeset, ),
         Badge(insert = NewBadge, update = BadgeChangeset, ),
+        GameMode(insert = NewGameMode, update = GameModeChangeset, ),
         Game(insert = NewGame, update = GameChangeset, ),
         Kill(insert = NewKill, update = KillChangeset, ),
         Map(insert = NewMap, update = MapChangeset, ),
@@ -0,0 +1,666 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Duration, NaiveDateTime, TimeZone, Utc};
+use diesel::prelude::*;
+use diesel::result::Error;
+use diesel::sql_query;
+use diesel::sql_types::{BigInt, Timestamptz};
+
+use crossout_log_common::game::{Fingerprint, Game, Round};
+use crossout_log_common::highlights::{detect_highlights, HighlightConfig};
+use crossout_log_common::log::{Payload, Spawn};
+
+use crate::db::DbConnection;
+use crate::rating::rate_round;
+use crate::schema::*;
+
+/// The maximum difference between the start times of two uploads of the same game.
+pub const START_TOLERANCE_SEC: i64 = 90;
+/// The maximum difference between the time stamps of the same kill or damage in two uploads.
+pub const EVENT_TOLERANCE_MS: i64 = 2000;
+/// The maximum number of rows inserted by a single statement.
+const INSERT_CHUNK: usize = 1000;
+
+macro_rules! find_or_insert_by_name {
+    ($conn:expr, $table:ident, $name:expr) => {
+        match $table::table
+            .filter($table::name.eq($name))
+            .select($table::id)
+            .first::<i32>($conn)
+            .optional()?
+        {
+            Some(id) => Ok(id),
+            None => diesel::insert_into($table::table)
+                .values($table::name.eq($name))
+                .returning($table::id)
+                .get_result::<i32>($conn),
+        }
+    };
+}
+
+#[derive(Insertable)]
+#[table_name = "damages"]
+struct NewDamage {
+    round_id: i32,
+    attacker_id: i32,
+    victim_id: i32,
+    weapon_id: i32,
+    value: f32,
+    flags: i32,
+    damage_ts: DateTime<Utc>,
+}
+
+fn utc(time_stamp: NaiveDateTime) -> DateTime<Utc> {
+    Utc.from_utc_datetime(&time_stamp)
+}
+
+/// Stores the games uploaded by the player with the `uploader` user id, and returns the ids of the stored games.
+///
+/// A game that was already uploaded by another participant is merged into the existing game: the rounds are matched by number, the spawns by player and the kills and damages by time stamp. Scores and stripes are only logged for some players, so they are taken from the first upload that contains them.
+/// Rounds without a finish cannot be stored. The ratings of the players are updated with every newly stored round.
+pub fn ingest_games(conn: &DbConnection, uploader: i64, games: &[Game]) -> QueryResult<Vec<i32>> {
+    conn.transaction::<_, Error, _>(|| {
+        games
+            .iter()
+            .map(|game| ingest_game(conn, uploader, game))
+            .collect()
+    })
+}
+
+fn ingest_game(conn: &DbConnection, uploader: i64, game: &Game) -> QueryResult<i32> {
+    let map_id = find_or_insert_by_name!(conn, maps, &game.map)?;
+    let game_mode_id = find_or_insert_by_name!(conn, game_modes, &game.game_mode)?;
+    let game_id = match find_game(conn, map_id, game_mode_id, &game.fingerprint())? {
+        Some(game_id) => {
+            merge_game(conn, game_id, game)?;
+            game_id
+        }
+        None => insert_game(conn, map_id, game_mode_id, game)?,
+    };
+    diesel::insert_into(game_uploads::table)
+        .values((
+            game_uploads::game_id.eq(game_id),
+            game_uploads::uploader.eq(uploader),
+        ))
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+    let series = game.series();
+    for round in game.rounds.iter() {
+        let side_swapped = series
+            .iter()
+            .flat_map(|s| s.rounds.iter())
+            .any(|r| r.round_no == round.round_no && r.side_swapped);
+        if let Some(round_id) = ingest_round(conn, game_id, round, side_swapped)? {
+            rate_round(conn, round_id)?;
+        }
+    }
+    Ok(game_id)
+}
+
+/// Finds a stored game with the same fingerprint.
+fn find_game(
+    conn: &DbConnection,
+    map_id: i32,
+    game_mode_id: i32,
+    fingerprint: &Fingerprint,
+) -> QueryResult<Option<i32>> {
+    let tolerance = Duration::seconds(START_TOLERANCE_SEC);
+    let start = utc(fingerprint.start);
+    let candidates: Vec<(i32, DateTime<Utc>)> = games::table
+        .filter(games::map_id.eq(map_id))
+        .filter(games::game_mode_id.eq(game_mode_id))
+        .filter(games::start_ts.between(start - tolerance, start + tolerance))
+        .select((games::id, games::start_ts))
+        .load(conn)?;
+    for (game_id, start_ts) in candidates {
+        let participants: Vec<(i64, i64)> = spawns::table
+            .inner_join(rounds::table)
+            .inner_join(players::table)
+            .filter(rounds::game_id.eq(game_id))
+            .filter(spawns::bot.eq(0))
+            .select((players::user_id, spawns::session))
+            .load(conn)?;
+        let stored = Fingerprint {
+            map: fingerprint.map.clone(),
+            game_mode: fingerprint.game_mode.clone(),
+            start: start_ts.naive_utc(),
+            user_ids: participants.iter().map(|(u, _)| *u as usize).collect(),
+            sessions: participants.iter().map(|(_, s)| *s as usize).collect(),
+        };
+        if stored.matches(fingerprint, tolerance) {
+            return Ok(Some(game_id));
+        }
+    }
+    Ok(None)
+}
+
+fn insert_game(
+    conn: &DbConnection,
+    map_id: i32,
+    game_mode_id: i32,
+    game: &Game,
+) -> QueryResult<i32> {
+    diesel::insert_into(games::table)
+        .values((
+            games::map_id.eq(map_id),
+            games::start_ts.eq(utc(game.start)),
+            games::game_mode_id.eq(game_mode_id),
+            games::level_no.eq(game.level_no as i16),
+            games::end_ts.eq(game.end.map(utc)),
+            games::duration.eq(game.duration().map(duration_sec)),
+            games::test_drive.eq(game.test_drive),
+            games::series_winner.eq(series_winner(game)),
+        ))
+        .returning(games::id)
+        .get_result(conn)
+}
+
+fn series_winner(game: &Game) -> Option<i16> {
+    game.series().and_then(|s| s.winner).map(i16::from)
+}
+
+/// Widens the time span of the stored game to include the upload, and completes the series winner.
+fn merge_game(conn: &DbConnection, game_id: i32, game: &Game) -> QueryResult<()> {
+    let (start_ts, end_ts, winner): (DateTime<Utc>, Option<DateTime<Utc>>, Option<i16>) =
+        games::table
+            .find(game_id)
+            .select((games::start_ts, games::end_ts, games::series_winner))
+            .first(conn)?;
+    let start_ts = start_ts.min(utc(game.start));
+    let end_ts = match (end_ts, game.end.map(utc)) {
+        (Some(lhs), Some(rhs)) => Some(lhs.max(rhs)),
+        (lhs, rhs) => lhs.or(rhs),
+    };
+    diesel::update(games::table.find(game_id))
+        .set((
+            games::start_ts.eq(start_ts),
+            games::end_ts.eq(end_ts),
+            games::duration.eq(end_ts.map(|end| duration_sec(end - start_ts))),
+            games::series_winner.eq(winner.or_else(|| series_winner(game))),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
+fn duration_sec(duration: Duration) -> f32 {
+    duration.num_milliseconds() as f32 / 1000.0
+}
+
+/// Stores the round and returns its id, unless the round is neither stored nor finished.
+fn ingest_round(
+    conn: &DbConnection,
+    game_id: i32,
+    round: &Round,
+    side_swapped: bool,
+) -> QueryResult<Option<i32>> {
+    let round_id = match rounds::table
+        .filter(rounds::game_id.eq(game_id))
+        .filter(rounds::round_no.eq(round.round_no as i16))
+        .select(rounds::id)
+        .first::<i32>(conn)
+        .optional()?
+    {
+        Some(round_id) => round_id,
+        None => match round.finish {
+            Some(ref finish) => diesel::insert_into(rounds::table)
+                .values((
+                    rounds::game_id.eq(game_id),
+                    rounds::start_ts.eq(utc(round.start)),
+                    rounds::round_no.eq(round.round_no as i16),
+                    rounds::duration.eq(finish.duration_sec),
+                    rounds::finish_reason.eq(finish.finish_reason),
+                    rounds::win_reason.eq(finish.win_reason),
+                    rounds::winning_team.eq(finish.winning_team as i16),
+                    rounds::side_swapped.eq(side_swapped),
+                ))
+                .returning(rounds::id)
+                .get_result(conn)?,
+            None => return Ok(None),
+        },
+    };
+
+    let players = round.resolver();
+    let mut by_no = HashMap::new();
+    for spawn in round.roster.iter() {
+        by_no.insert(spawn.player_no, ingest_spawn(conn, round_id, round, spawn)?);
+    }
+    let spawn_id = |spawn: Option<&Spawn>| spawn.and_then(|s| by_no.get(&s.player_no).copied());
+    let spawn_ids: Vec<i32> = by_no.values().copied().collect();
+    let scored: HashSet<i32> = scores::table
+        .filter(scores::spawn_id.eq_any(&spawn_ids))
+        .select(scores::spawn_id)
+        .load::<i32>(conn)?
+        .into_iter()
+        .collect();
+    let striped: HashSet<i32> = stripes::table
+        .filter(stripes::spawn_id.eq_any(&spawn_ids))
+        .select(stripes::spawn_id)
+        .load::<i32>(conn)?
+        .into_iter()
+        .collect();
+
+    // the assists following a kill, are only stored if the kill is new
+    let mut last_kill = None;
+    let mut weapon_ids = HashMap::new();
+    let mut damages = Vec::new();
+    // the weapon of the last hit of an attacker on a victim in the current life of the victim, the finishing blow of a kill
+    let mut last_hit = HashMap::<(i32, i32), i32>::new();
+    for entry in round.entries.iter() {
+        match entry.message {
+            Payload::Damage(ref damage) => {
+                if let (Some(attacker_id), Some(victim_id)) = (
+                    spawn_id(players.actor(&entry.message)),
+                    spawn_id(players.victim(&entry.message)),
+                ) {
+                    let weapon_id = weapon_id(conn, &mut weapon_ids, &damage.weapon)?;
+                    last_hit.insert((attacker_id, victim_id), weapon_id);
+                    damages.push(NewDamage {
+                        round_id,
+                        attacker_id,
+                        victim_id,
+                        weapon_id,
+                        value: damage.value,
+                        flags: damage.flags.bits() as i32,
+                        damage_ts: utc(entry.time_stamp),
+                    });
+                }
+            }
+            Payload::Kill(_) => {
+                let victim_id = spawn_id(players.victim(&entry.message));
+                last_kill = match (spawn_id(players.actor(&entry.message)), victim_id) {
+                    (Some(killer_id), Some(victim_id)) => insert_kill(
+                        conn,
+                        round_id,
+                        killer_id,
+                        victim_id,
+                        last_hit.get(&(killer_id, victim_id)).copied(),
+                        entry.time_stamp,
+                    )?,
+                    _ => None,
+                };
+                // the hits of an earlier life are no finishing blow
+                if let Some(victim_id) = victim_id {
+                    last_hit.retain(|&(_, victim), _| victim != victim_id);
+                }
+            }
+            Payload::Assist(ref assist) => {
+                if let (Some(kill_id), Some(assistant_id)) =
+                    (last_kill, spawn_id(players.actor(&entry.message)))
+                {
+                    let weapon_id = weapon_id(conn, &mut weapon_ids, &assist.weapon)?;
+                    diesel::insert_into(assists::table)
+                        .values((
+                            assists::kill_id.eq(kill_id),
+                            assists::assistant_id.eq(assistant_id),
+                            assists::weapon_id.eq(weapon_id),
+                            assists::elapsed_sec.eq(assist.elapsed_sec),
+                            assists::damage_dealt.eq(assist.damage_dealt),
+                            assists::damage_flags.eq(assist.damage_flags.bits() as i32),
+                        ))
+                        .execute(conn)?;
+                }
+            }
+            Payload::Score(ref score) => {
+                if let Some(spawn_id) = spawn_id(players.actor(&entry.message)) {
+                    if !scored.contains(&spawn_id) {
+                        diesel::insert_into(scores::table)
+                            .values((
+                                scores::spawn_id.eq(spawn_id),
+                                scores::value.eq(score.value),
+                                scores::reason.eq(score.reason),
+                            ))
+                            .execute(conn)?;
+                    }
+                }
+            }
+            Payload::Stripe(ref stripe) => {
+                if let Some(spawn_id) = spawn_id(players.actor(&entry.message)) {
+                    if !striped.contains(&spawn_id) {
+                        let badge_id = find_or_insert_by_name!(conn, badges, &stripe.name)?;
+                        diesel::insert_into(stripes::table)
+                            .values((
+                                stripes::badge_id.eq(badge_id),
+                                stripes::spawn_id.eq(spawn_id),
+                                stripes::value.eq(stripe.value as f32),
+                            ))
+                            .execute(conn)?;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    insert_damages(conn, round_id, damages)?;
+    insert_highlights(conn, round_id, round, &by_no)?;
+    Ok(Some(round_id))
+}
+
+/// Detects and inserts the highlights of the round, unless another upload of the round already stored them.
+fn insert_highlights(
+    conn: &DbConnection,
+    round_id: i32,
+    round: &Round,
+    spawn_ids: &HashMap<u8, i32>,
+) -> QueryResult<()> {
+    let stored = highlights::table
+        .filter(highlights::round_id.eq(round_id))
+        .count()
+        .get_result::<i64>(conn)?
+        > 0;
+    if stored {
+        return Ok(());
+    }
+    let players = round.resolver();
+    let spawn_id = |nick_name: &str| {
+        players
+            .by_nick(nick_name)
+            .and_then(|s| spawn_ids.get(&s.player_no).copied())
+    };
+    for highlight in detect_highlights(round, &HighlightConfig::default()) {
+        if let Some(player_spawn_id) = spawn_id(&highlight.player) {
+            diesel::insert_into(highlights::table)
+                .values((
+                    highlights::round_id.eq(round_id),
+                    highlights::spawn_id.eq(player_spawn_id),
+                    highlights::kind.eq(highlight.kind),
+                    highlights::kills.eq(highlight.kills as i16),
+                    highlights::other_id.eq(highlight.other.as_deref().and_then(spawn_id)),
+                    highlights::highlight_ts.eq(utc(highlight.time_stamp)),
+                ))
+                .execute(conn)?;
+        }
+    }
+    Ok(())
+}
+
+fn weapon_id<'a>(
+    conn: &DbConnection,
+    cache: &mut HashMap<&'a str, i32>,
+    name: &'a str,
+) -> QueryResult<i32> {
+    if let Some(&weapon_id) = cache.get(name) {
+        return Ok(weapon_id);
+    }
+    let weapon_id = find_or_insert_by_name!(conn, weapons, name)?;
+    cache.insert(name, weapon_id);
+    Ok(weapon_id)
+}
+
+/// Inserts the damages not yet stored by another upload of the round.
+fn insert_damages(
+    conn: &DbConnection,
+    round_id: i32,
+    mut damages: Vec<NewDamage>,
+) -> QueryResult<()> {
+    let known: Vec<(i32, i32, i32, f32, DateTime<Utc>)> = damages::table
+        .filter(damages::round_id.eq(round_id))
+        .select((
+            damages::attacker_id,
+            damages::victim_id,
+            damages::weapon_id,
+            damages::value,
+            damages::damage_ts,
+        ))
+        .load(conn)?;
+    retain_unknown_damages(&mut damages, known);
+    for chunk in damages.chunks(INSERT_CHUNK) {
+        diesel::insert_into(damages::table)
+            .values(chunk)
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+/// Removes the damages matching a `known` damage by attacker, victim, weapon and value, within [`EVENT_TOLERANCE_MS`] of its time stamp. A known damage matches at most one damage, so repeated equal hits are only removed as often as they are known.
+fn retain_unknown_damages(
+    damages: &mut Vec<NewDamage>,
+    known: Vec<(i32, i32, i32, f32, DateTime<Utc>)>,
+) {
+    if known.is_empty() {
+        return;
+    }
+    let mut index = HashMap::<_, Vec<DateTime<Utc>>>::new();
+    for (attacker_id, victim_id, weapon_id, value, damage_ts) in known {
+        index
+            .entry((attacker_id, victim_id, weapon_id, value.to_bits()))
+            .or_default()
+            .push(damage_ts);
+    }
+    damages.retain(|d| {
+        let key = (d.attacker_id, d.victim_id, d.weapon_id, d.value.to_bits());
+        let known = match index.get_mut(&key) {
+            Some(known) => known,
+            None => return true,
+        };
+        let matched = known
+            .iter()
+            .position(|ts| (*ts - d.damage_ts).num_milliseconds().abs() <= EVENT_TOLERANCE_MS);
+        match matched {
+            Some(i) => {
+                known.swap_remove(i);
+                false
+            }
+            None => true,
+        }
+    });
+}
+
+fn ingest_spawn(
+    conn: &DbConnection,
+    round_id: i32,
+    round: &Round,
+    spawn: &Spawn,
+) -> QueryResult<i32> {
+    let player_id = ingest_player(conn, spawn, utc(round.start))?;
+    if let Some(spawn_id) = spawns::table
+        .filter(spawns::round_id.eq(round_id))
+        .filter(spawns::player_id.eq(player_id))
+        .select(spawns::id)
+        .first::<i32>(conn)
+        .optional()?
+    {
+        return Ok(spawn_id);
+    }
+    let spawn_counter = round
+        .entries
+        .iter()
+        .filter_map(|e| match e.message {
+            Payload::Player(ref p) if p.player_no == spawn.player_no => Some(p.spawn_counter),
+            _ => None,
+        })
+        .max()
+        .unwrap_or_default();
+    let spawn_id = diesel::insert_into(spawns::table)
+        .values((
+            spawns::player_id.eq(player_id),
+            spawns::round_id.eq(round_id),
+            spawns::spawn_counter.eq(spawn_counter as i16),
+            spawns::player_no.eq(spawn.player_no as i16),
+            spawns::team.eq(spawn.team as i16),
+            spawns::bot.eq(spawn.bot as i16),
+            spawns::party.eq(spawn.party_id as i64),
+            spawns::session.eq(spawn.session as i64),
+            spawns::design.eq(spawn.design_hash as i64),
+        ))
+        .returning(spawns::id)
+        .get_result(conn)?;
+    if spawn.bot == 0 {
+        ingest_design(conn, spawn.design_hash as i64, utc(round.start))?;
+    }
+    Ok(spawn_id)
+}
+
+/// Records when the design was seen and assigns it to the human player who spawned it most often.
+fn ingest_design(conn: &DbConnection, hash: i64, seen: DateTime<Utc>) -> QueryResult<()> {
+    diesel::insert_into(designs::table)
+        .values((
+            designs::hash.eq(hash),
+            designs::first_seen.eq(seen),
+            designs::last_seen.eq(seen),
+        ))
+        .on_conflict(designs::hash)
+        .do_nothing()
+        .execute(conn)?;
+    // the same owner as assigned by the migration: most spawns, then the earliest spawn
+    sql_query(
+        "UPDATE designs d SET
+            first_seen = least(d.first_seen, $2),
+            last_seen = greatest(d.last_seen, $2),
+            owner_id = (
+                SELECT s.player_id FROM spawns s
+                WHERE s.design = d.hash AND s.bot = 0
+                GROUP BY s.player_id
+                ORDER BY count(*) DESC, min(s.id)
+                LIMIT 1
+            )
+        WHERE d.hash = $1",
+    )
+    .bind::<BigInt, _>(hash)
+    .bind::<Timestamptz, _>(seen)
+    .execute(conn)?;
+    Ok(())
+}
+
+/// Finds or inserts the player of the spawn and records the nickname in the name history.
+///
+/// Players are identified by their user id, so a renamed player keeps the statistics. Bots share the user id 0 and are identified by their nickname instead.
+fn ingest_player(conn: &DbConnection, spawn: &Spawn, seen: DateTime<Utc>) -> QueryResult<i32> {
+    let user_id = spawn.user_id as i64;
+    let mut query = players::table
+        .filter(players::user_id.eq(user_id))
+        .select(players::id)
+        .into_boxed();
+    if user_id == 0 {
+        query = query.filter(players::name.eq(&spawn.nick_name));
+    }
+    let player_id = match query.first::<i32>(conn).optional()? {
+        Some(player_id) => player_id,
+        None => diesel::insert_into(players::table)
+            .values((
+                players::user_id.eq(user_id),
+                players::name.eq(&spawn.nick_name),
+            ))
+            .returning(players::id)
+            .get_result(conn)?,
+    };
+
+    let known = player_names::table
+        .filter(player_names::player_id.eq(player_id))
+        .filter(player_names::name.eq(&spawn.nick_name))
+        .select((player_names::id, player_names::first_seen, player_names::last_seen))
+        .first::<(i32, DateTime<Utc>, DateTime<Utc>)>(conn)
+        .optional()?;
+    match known {
+        Some((id, first_seen, last_seen)) => {
+            diesel::update(player_names::table.find(id))
+                .set((
+                    player_names::first_seen.eq(first_seen.min(seen)),
+                    player_names::last_seen.eq(last_seen.max(seen)),
+                ))
+                .execute(conn)?;
+        }
+        None => {
+            diesel::insert_into(player_names::table)
+                .values((
+                    player_names::player_id.eq(player_id),
+                    player_names::name.eq(&spawn.nick_name),
+                    player_names::first_seen.eq(seen),
+                    player_names::last_seen.eq(seen),
+                ))
+                .execute(conn)?;
+        }
+    }
+    // the player is listed with the most recent nickname, older uploads do not rename it
+    let latest: String = player_names::table
+        .filter(player_names::player_id.eq(player_id))
+        .order(player_names::last_seen.desc())
+        .select(player_names::name)
+        .first(conn)?;
+    diesel::update(players::table.find(player_id))
+        .set(players::name.eq(latest))
+        .execute(conn)?;
+    Ok(player_id)
+}
+
+/// Inserts the kill with the weapon of the finishing blow, unless it is already stored. Returns the id of the new kill.
+fn insert_kill(
+    conn: &DbConnection,
+    round_id: i32,
+    killer_id: i32,
+    victim_id: i32,
+    weapon_id: Option<i32>,
+    time_stamp: NaiveDateTime,
+) -> QueryResult<Option<i32>> {
+    let kill_ts = utc(time_stamp);
+    let tolerance = Duration::milliseconds(EVENT_TOLERANCE_MS);
+    let known = kills::table
+        .filter(kills::round_id.eq(round_id))
+        .filter(kills::killer_id.eq(killer_id))
+        .filter(kills::victim_id.eq(victim_id))
+        .filter(kills::kill_ts.between(kill_ts - tolerance, kill_ts + tolerance))
+        .select(kills::id)
+        .first::<i32>(conn)
+        .optional()?;
+    if known.is_some() {
+        return Ok(None);
+    }
+    diesel::insert_into(kills::table)
+        .values((
+            kills::round_id.eq(round_id),
+            kills::killer_id.eq(killer_id),
+            kills::victim_id.eq(victim_id),
+            kills::kill_ts.eq(kill_ts),
+            kills::weapon_id.eq(weapon_id),
+        ))
+        .returning(kills::id)
+        .get_result(conn)
+        .map(Some)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn damage(attacker_id: i32, value: f32, damage_ts: DateTime<Utc>) -> NewDamage {
+        NewDamage {
+            round_id: 1,
+            attacker_id,
+            victim_id: 2,
+            weapon_id: 3,
+            value,
+            flags: 0,
+            damage_ts,
+        }
+    }
+
+    #[test]
+    fn test_retain_unknown_damages() {
+        let start = Utc.with_ymd_and_hms(2022, 6, 1, 20, 0, 30).unwrap();
+        let at = |ms: i64| start + Duration::milliseconds(ms);
+        // a stream of equal hits, two of which the other client logged as well
+        let mut damages = vec![
+            damage(1, 10.0, at(50)),
+            damage(1, 10.0, at(150)),
+            damage(1, 10.0, at(250)),
+            damage(1, 10.0, at(EVENT_TOLERANCE_MS + 101)),
+            damage(1, 12.5, at(0)),
+            damage(4, 10.0, at(0)),
+        ];
+        let known = vec![(1, 2, 3, 10.0, at(0)), (1, 2, 3, 10.0, at(100))];
+        retain_unknown_damages(&mut damages, known);
+        let retained: Vec<(i32, f32, DateTime<Utc>)> = damages
+            .iter()
+            .map(|d| (d.attacker_id, d.value, d.damage_ts))
+            .collect();
+        let later = at(EVENT_TOLERANCE_MS + 101);
+        assert_eq!(
+            retained,
+            vec![
+                (1, 10.0, at(250)),
+                (1, 10.0, later),
+                (1, 12.5, at(0)),
+                (4, 10.0, at(0))
+            ]
+        );
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::result::Error;
+use serde::Serialize;
+
+use crate::db::DbConnection;
+use crate::schema::*;
+
+/// The rating of a player without rated rounds.
+pub const DEFAULT_RATING: f64 = 1500.0;
+/// The maximum rating change of a single round.
+pub const K_FACTOR: f64 = 32.0;
+
+/// A rating difference of this many points means the higher rated team wins 10 out of 11 rounds.
+const SCALE: f64 = 400.0;
+
+#[derive(Debug, Clone, Queryable, Serialize)]
+pub struct PlayerRating {
+    pub user_id: i64,
+    pub name: String,
+    pub rating: f64,
+    pub rounds: i32,
+    pub updated_ts: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Queryable, Serialize)]
+pub struct RatingChange {
+    pub game_id: i32,
+    pub round_id: i32,
+    /// The rating after the round.
+    pub rating: f64,
+    pub delta: f64,
+    pub rating_ts: DateTime<Utc>,
+}
+
+/// The rating change of each player of a round, a team Elo: every player is rated against the average rating of the opposing teams, using the average rating of the own team.
+///
+/// `players` holds the player id, team and current rating. Returns nothing if the round had fewer than two teams or the winning team did not play.
+pub fn elo_deltas(players: &[(i32, i16, f64)], winning_team: i16) -> Vec<(i32, f64)> {
+    let mut teams = BTreeMap::<i16, (f64, usize)>::new();
+    for &(_, team, rating) in players {
+        let (sum, count) = teams.entry(team).or_default();
+        *sum += rating;
+        *count += 1;
+    }
+    if teams.len() < 2 || !teams.contains_key(&winning_team) {
+        return Vec::new();
+    }
+    let averages: BTreeMap<i16, f64> = teams
+        .into_iter()
+        .map(|(team, (sum, count))| (team, sum / count as f64))
+        .collect();
+    players
+        .iter()
+        .map(|&(player_id, team, _)| {
+            let own = averages[&team];
+            let opponents: Vec<f64> = averages
+                .iter()
+                .filter(|(&t, _)| t != team)
+                .map(|(_, &r)| r)
+                .collect();
+            let opponent = opponents.iter().sum::<f64>() / opponents.len() as f64;
+            let expected = 1.0 / (1.0 + 10f64.powf((opponent - own) / SCALE));
+            let score = if team == winning_team { 1.0 } else { 0.0 };
+            (player_id, K_FACTOR * (score - expected))
+        })
+        .collect()
+}
+
+/// Updates the ratings of the human players of the round. Rounds which are already rated and the rounds of test drives are skipped. Returns whether the round was rated.
+pub fn rate_round(conn: &DbConnection, round_id: i32) -> QueryResult<bool> {
+    let rated = rating_history::table
+        .filter(rating_history::round_id.eq(round_id))
+        .count()
+        .get_result::<i64>(conn)?
+        > 0;
+    if rated {
+        return Ok(false);
+    }
+    let (winning_team, start_ts, test_drive): (i16, DateTime<Utc>, bool) = rounds::table
+        .inner_join(games::table)
+        .filter(rounds::id.eq(round_id))
+        .select((rounds::winning_team, rounds::start_ts, games::test_drive))
+        .first(conn)?;
+    if test_drive {
+        return Ok(false);
+    }
+    let spawns: Vec<(i32, i16)> = spawns::table
+        .filter(spawns::round_id.eq(round_id))
+        .filter(spawns::bot.eq(0))
+        .select((spawns::player_id, spawns::team))
+        .load(conn)?;
+    let player_ids: Vec<i32> = spawns.iter().map(|&(player_id, _)| player_id).collect();
+    let current: HashMap<i32, f64> = ratings::table
+        .filter(ratings::player_id.eq_any(&player_ids))
+        .select((ratings::player_id, ratings::rating))
+        .load(conn)?
+        .into_iter()
+        .collect();
+    let players: Vec<(i32, i16, f64)> = spawns
+        .into_iter()
+        .map(|(player_id, team)| {
+            let rating = current.get(&player_id).copied().unwrap_or(DEFAULT_RATING);
+            (player_id, team, rating)
+        })
+        .collect();
+    let deltas = elo_deltas(&players, winning_team);
+    for &(player_id, delta) in deltas.iter() {
+        let rating = current.get(&player_id).copied().unwrap_or(DEFAULT_RATING) + delta;
+        diesel::insert_into(ratings::table)
+            .values((
+                ratings::player_id.eq(player_id),
+                ratings::rating.eq(rating),
+                ratings::rounds.eq(1),
+                ratings::updated_ts.eq(start_ts),
+            ))
+            .on_conflict(ratings::player_id)
+            .do_update()
+            .set((
+                ratings::rating.eq(rating),
+                ratings::rounds.eq(ratings::rounds + 1),
+                ratings::updated_ts.eq(start_ts),
+            ))
+            .execute(conn)?;
+        diesel::insert_into(rating_history::table)
+            .values((
+                rating_history::player_id.eq(player_id),
+                rating_history::round_id.eq(round_id),
+                rating_history::rating.eq(rating),
+                rating_history::delta.eq(delta),
+                rating_history::rating_ts.eq(start_ts),
+            ))
+            .execute(conn)?;
+    }
+    Ok(!deltas.is_empty())
+}
+
+/// Discards all ratings and rates every round again in the order they were played. Incremental updates depend on the upload order, a recomputation does not. Returns the number of rated rounds.
+pub fn recompute_ratings(conn: &DbConnection) -> QueryResult<usize> {
+    conn.transaction::<_, Error, _>(|| {
+        diesel::delete(rating_history::table).execute(conn)?;
+        diesel::delete(ratings::table).execute(conn)?;
+        let round_ids: Vec<i32> = rounds::table
+            .inner_join(games::table)
+            .filter(games::test_drive.eq(false))
+            .order((rounds::start_ts, rounds::id))
+            .select(rounds::id)
+            .load(conn)?;
+        let mut rated = 0;
+        for round_id in round_ids {
+            if rate_round(conn, round_id)? {
+                rated += 1;
+            }
+        }
+        Ok(rated)
+    })
+}
+
+/// The players with the highest ratings.
+pub fn top_ratings(conn: &DbConnection, limit: i64) -> QueryResult<Vec<PlayerRating>> {
+    ratings::table
+        .inner_join(players::table)
+        .order(ratings::rating.desc())
+        .limit(limit)
+        .select((
+            players::user_id,
+            players::name,
+            ratings::rating,
+            ratings::rounds,
+            ratings::updated_ts,
+        ))
+        .load(conn)
+}
+
+/// The rating changes of the player with the user id, oldest first.
+pub fn rating_changes(conn: &DbConnection, user_id: i64) -> QueryResult<Vec<RatingChange>> {
+    rating_history::table
+        .inner_join(players::table)
+        .inner_join(rounds::table)
+        .filter(players::user_id.eq(user_id))
+        .order((rating_history::rating_ts, rating_history::id))
+        .select((
+            rounds::game_id,
+            rating_history::round_id,
+            rating_history::rating,
+            rating_history::delta,
+            rating_history::rating_ts,
+        ))
+        .load(conn)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_elo_deltas() {
+        // equal teams gain and lose half the K factor
+        let deltas = elo_deltas(&[(1, 1, 1500.0), (2, 1, 1500.0), (3, 2, 1500.0)], 1);
+        assert_eq!(deltas, vec![(1, 16.0), (2, 16.0), (3, -16.0)]);
+        // an upset moves the ratings more than an expected win
+        let upset = elo_deltas(&[(1, 1, 1400.0), (2, 2, 1800.0)], 1);
+        let expected = elo_deltas(&[(1, 1, 1400.0), (2, 2, 1800.0)], 2);
+        assert!((upset[0].1 - K_FACTOR * 10.0 / 11.0).abs() < 1e-9);
+        assert!((expected[1].1 - K_FACTOR / 11.0).abs() < 1e-9);
+        assert_eq!(upset[0].1 + upset[1].1, 0.0);
+        assert!(elo_deltas(&[(1, 1, 1500.0), (2, 1, 1500.0)], 1).is_empty());
+        assert!(elo_deltas(&[(1, 1, 1500.0), (2, 2, 1500.0)], 3).is_empty());
+    }
+}
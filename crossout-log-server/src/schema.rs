@@ -1,4 +1,6 @@
 table! {
+    use diesel::sql_types::*;
+
     assists (id) {
         id -> Int4,
         kill_id -> Int4,
@@ -11,6 +13,8 @@ table! {
 }
 
 table! {
+    use diesel::sql_types::*;
+
     badges (id) {
         id -> Int4,
         name -> Varchar,
@@ -18,23 +22,100 @@ table! {
 }
 
 table! {
+    use diesel::sql_types::*;
+
+    damages (id) {
+        id -> Int4,
+        round_id -> Int4,
+        attacker_id -> Int4,
+        victim_id -> Int4,
+        weapon_id -> Int4,
+        value -> Float4,
+        flags -> Int4,
+        damage_ts -> Timestamptz,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    designs (id) {
+        id -> Int4,
+        hash -> Int8,
+        owner_id -> Nullable<Int4>,
+        label -> Nullable<Varchar>,
+        first_seen -> Timestamptz,
+        last_seen -> Timestamptz,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    game_modes (id) {
+        id -> Int4,
+        name -> Varchar,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    game_uploads (id) {
+        id -> Int4,
+        game_id -> Int4,
+        uploader -> Int8,
+        upload_ts -> Timestamptz,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
     games (id) {
         id -> Int4,
         map_id -> Int4,
         start_ts -> Timestamptz,
+        game_mode_id -> Nullable<Int4>,
+        level_no -> Int2,
+        end_ts -> Nullable<Timestamptz>,
+        duration -> Nullable<Float4>,
+        test_drive -> Bool,
+        series_winner -> Nullable<Int2>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crossout_log_common::highlights::HighlightKindMapping;
+
+    highlights (id) {
+        id -> Int4,
+        round_id -> Int4,
+        spawn_id -> Int4,
+        kind -> HighlightKindMapping,
+        kills -> Int2,
+        other_id -> Nullable<Int4>,
+        highlight_ts -> Timestamptz,
     }
 }
 
 table! {
+    use diesel::sql_types::*;
+
     kills (id) {
         id -> Int4,
         round_id -> Int4,
         killer_id -> Int4,
         victim_id -> Int4,
+        kill_ts -> Nullable<Timestamptz>,
+        weapon_id -> Nullable<Int4>,
     }
 }
 
 table! {
+    use diesel::sql_types::*;
+
     maps (id) {
         id -> Int4,
         name -> Varchar,
@@ -42,6 +123,20 @@ table! {
 }
 
 table! {
+    use diesel::sql_types::*;
+
+    player_names (id) {
+        id -> Int4,
+        player_id -> Int4,
+        name -> Varchar,
+        first_seen -> Timestamptz,
+        last_seen -> Timestamptz,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
     players (id) {
         id -> Int4,
         user_id -> Int8,
@@ -50,28 +145,62 @@ table! {
 }
 
 table! {
+    use diesel::sql_types::*;
+
+    rating_history (id) {
+        id -> Int4,
+        player_id -> Int4,
+        round_id -> Int4,
+        rating -> Float8,
+        delta -> Float8,
+        rating_ts -> Timestamptz,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    ratings (id) {
+        id -> Int4,
+        player_id -> Int4,
+        rating -> Float8,
+        rounds -> Int4,
+        updated_ts -> Timestamptz,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crossout_log_common::log::{FinishReasonMapping, WinReasonMapping};
+
     rounds (id) {
         id -> Int4,
         game_id -> Int4,
         start_ts -> Timestamptz,
         round_no -> Int2,
         duration -> Float4,
-        finish_reason -> Int2,
-        win_reason -> Int2,
-        winning_team -> Float4,
+        finish_reason -> FinishReasonMapping,
+        win_reason -> WinReasonMapping,
+        winning_team -> Int2,
+        side_swapped -> Bool,
     }
 }
 
 table! {
+    use diesel::sql_types::*;
+    use crossout_log_common::log::ScoreReasonMapping;
+
     scores (id) {
         id -> Int4,
         spawn_id -> Int4,
         value -> Float4,
-        reason -> Int2,
+        reason -> ScoreReasonMapping,
     }
 }
 
 table! {
+    use diesel::sql_types::*;
+
     spawns (id) {
         id -> Int4,
         player_id -> Int4,
@@ -87,6 +216,8 @@ table! {
 }
 
 table! {
+    use diesel::sql_types::*;
+
     stripes (id) {
         id -> Int4,
         badge_id -> Int4,
@@ -96,6 +227,8 @@ table! {
 }
 
 table! {
+    use diesel::sql_types::*;
+
     weapons (id) {
         id -> Int4,
         name -> Varchar,
@@ -105,8 +238,19 @@ table! {
 joinable!(assists -> kills (kill_id));
 joinable!(assists -> spawns (assistant_id));
 joinable!(assists -> weapons (weapon_id));
+joinable!(damages -> rounds (round_id));
+joinable!(damages -> weapons (weapon_id));
+joinable!(designs -> players (owner_id));
+joinable!(game_uploads -> games (game_id));
+joinable!(games -> game_modes (game_mode_id));
 joinable!(games -> maps (map_id));
+joinable!(highlights -> rounds (round_id));
 joinable!(kills -> rounds (round_id));
+joinable!(kills -> weapons (weapon_id));
+joinable!(player_names -> players (player_id));
+joinable!(rating_history -> players (player_id));
+joinable!(rating_history -> rounds (round_id));
+joinable!(ratings -> players (player_id));
 joinable!(rounds -> games (game_id));
 joinable!(scores -> spawns (spawn_id));
 joinable!(spawns -> players (player_id));
@@ -117,10 +261,18 @@ joinable!(stripes -> spawns (spawn_id));
 allow_tables_to_appear_in_same_query!(
     assists,
     badges,
+    damages,
+    designs,
+    game_modes,
+    game_uploads,
     games,
+    highlights,
     kills,
     maps,
+    player_names,
     players,
+    rating_history,
+    ratings,
     rounds,
     scores,
     spawns,
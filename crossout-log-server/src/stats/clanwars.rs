@@ -0,0 +1,135 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::{
+    Array, BigInt, Bool, Float, Integer, Nullable, SmallInt, Timestamptz, Varchar,
+};
+use serde::Serialize;
+
+use crossout_log_common::log::{WinReason, WinReasonMapping};
+
+use crate::db::DbConnection;
+use crate::stats::{bind_game_filter, GameFilter, FILTERED_GAMES};
+
+/// A best of three series. Teams are numbered as in the first round.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClanWar {
+    pub id: i32,
+    pub start_ts: DateTime<Utc>,
+    pub map: String,
+    pub series_winner: Option<i16>,
+    pub rounds: Vec<SeriesRound>,
+    /// The nicknames of the players of each team in the first round.
+    pub teams: BTreeMap<i16, Vec<String>>,
+}
+
+#[derive(Debug, Clone, QueryableByName, Serialize)]
+pub struct SeriesRound {
+    #[serde(skip)]
+    #[sql_type = "Integer"]
+    game_id: i32,
+    #[sql_type = "SmallInt"]
+    pub round_no: i16,
+    /// The winning team as reported by the round finish.
+    #[sql_type = "SmallInt"]
+    pub winning_team: i16,
+    #[sql_type = "WinReasonMapping"]
+    pub win_reason: WinReason,
+    #[sql_type = "Float"]
+    pub duration: f32,
+    #[sql_type = "Bool"]
+    pub side_swapped: bool,
+}
+
+#[derive(QueryableByName)]
+struct SeriesRow {
+    #[sql_type = "Integer"]
+    id: i32,
+    #[sql_type = "Timestamptz"]
+    start_ts: DateTime<Utc>,
+    #[sql_type = "Varchar"]
+    map: String,
+    #[sql_type = "Nullable<SmallInt>"]
+    series_winner: Option<i16>,
+}
+
+#[derive(QueryableByName)]
+struct TeamRow {
+    #[sql_type = "Integer"]
+    game_id: i32,
+    #[sql_type = "SmallInt"]
+    team: i16,
+    #[sql_type = "Varchar"]
+    name: String,
+}
+
+/// The most recent series, newest first.
+pub fn clan_wars(
+    conn: &DbConnection,
+    limit: i64,
+    filter: &GameFilter,
+) -> QueryResult<Vec<ClanWar>> {
+    let series: Vec<SeriesRow> = bind_game_filter!(
+        sql_query(format!(
+            "WITH filtered AS ({})
+            SELECT g.id, g.start_ts, m.name AS map, g.series_winner
+            FROM games g
+            JOIN maps m ON m.id = g.map_id
+            WHERE g.id IN (SELECT id FROM filtered)
+            AND g.series_winner IS NOT NULL
+            ORDER BY g.start_ts DESC
+            LIMIT $5",
+            FILTERED_GAMES
+        )),
+        filter
+    )
+    .bind::<BigInt, _>(limit)
+    .load(conn)?;
+    let game_ids: Vec<i32> = series.iter().map(|s| s.id).collect();
+    let rounds: Vec<SeriesRound> = sql_query(
+        "SELECT r.game_id, r.round_no, r.winning_team, r.win_reason, r.duration, r.side_swapped
+        FROM rounds r
+        WHERE r.game_id = ANY($1)
+        ORDER BY r.round_no",
+    )
+    .bind::<Array<Integer>, _>(&game_ids)
+    .load(conn)?;
+    let teams: Vec<TeamRow> = sql_query(
+        "SELECT r.game_id, s.team, p.name
+        FROM spawns s
+        JOIN rounds r ON r.id = s.round_id
+        JOIN players p ON p.id = s.player_id
+        WHERE r.game_id = ANY($1)
+        AND r.round_no = (SELECT min(f.round_no) FROM rounds f WHERE f.game_id = r.game_id)
+        ORDER BY s.player_no",
+    )
+    .bind::<Array<Integer>, _>(&game_ids)
+    .load(conn)?;
+
+    let mut rounds_by_game = HashMap::<_, Vec<_>>::new();
+    for round in rounds {
+        rounds_by_game.entry(round.game_id).or_default().push(round);
+    }
+    let mut teams_by_game = HashMap::<_, BTreeMap<i16, Vec<String>>>::new();
+    for row in teams {
+        teams_by_game
+            .entry(row.game_id)
+            .or_default()
+            .entry(row.team)
+            .or_default()
+            .push(row.name);
+    }
+    Ok(series
+        .into_iter()
+        .map(|s| ClanWar {
+            rounds: rounds_by_game.remove(&s.id).unwrap_or_default(),
+            teams: teams_by_game.remove(&s.id).unwrap_or_default(),
+            id: s.id,
+            start_ts: s.start_ts,
+            map: s.map,
+            series_winner: s.series_winner,
+        })
+        .collect())
+}
@@ -0,0 +1,87 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::{BigInt, Double, Nullable, Timestamptz, Varchar};
+use serde::Serialize;
+
+use crate::db::DbConnection;
+use crate::schema::designs;
+use crate::stats::{bind_game_filter, GameFilter, FILTERED_GAMES, SPAWN_STATS};
+
+/// The performance of a vehicle build, identified by its design hash, over the rounds of the human players.
+#[derive(Debug, Clone, QueryableByName, Serialize)]
+pub struct DesignStats {
+    #[sql_type = "BigInt"]
+    pub hash: i64,
+    /// The name given to the design by a user.
+    #[sql_type = "Nullable<Varchar>"]
+    pub label: Option<String>,
+    /// The user id of the player who spawned the design most often.
+    #[sql_type = "Nullable<BigInt>"]
+    pub owner_user_id: Option<i64>,
+    #[sql_type = "Nullable<Varchar>"]
+    pub owner: Option<String>,
+    #[sql_type = "Timestamptz"]
+    pub first_seen: DateTime<Utc>,
+    #[sql_type = "Timestamptz"]
+    pub last_seen: DateTime<Utc>,
+    #[sql_type = "BigInt"]
+    pub rounds: i64,
+    #[sql_type = "Double"]
+    pub win_rate: f64,
+    #[sql_type = "Double"]
+    pub average_kills: f64,
+    #[sql_type = "Double"]
+    pub average_damage: f64,
+    #[sql_type = "Double"]
+    pub average_score: f64,
+}
+
+/// Aggregates the rounds of all designs, or of the design with the hash, optionally only the designs owned by the player with the user id. Ordered by the number of rounds, most first.
+pub fn design_stats(
+    conn: &DbConnection,
+    hash: Option<i64>,
+    owner_user_id: Option<i64>,
+    limit: i64,
+    filter: &GameFilter,
+) -> QueryResult<Vec<DesignStats>> {
+    bind_game_filter!(
+        sql_query(format!(
+            "WITH filtered AS ({filtered}),
+            stats AS (
+                {spawn_stats}
+                AND s.bot = 0
+                AND r.game_id IN (SELECT id FROM filtered)
+            )
+            SELECT d.hash, d.label, o.user_id AS owner_user_id, o.name AS owner,
+                d.first_seen, d.last_seen, count(*) AS rounds,
+                avg(stats.won)::double precision AS win_rate,
+                avg(stats.kills)::double precision AS average_kills,
+                avg(stats.damage) AS average_damage,
+                avg(stats.score) AS average_score
+            FROM stats
+            JOIN designs d ON d.hash = stats.design
+            LEFT JOIN players o ON o.id = d.owner_id
+            WHERE ($5::bigint IS NULL OR d.hash = $5)
+            AND ($6::bigint IS NULL OR o.user_id = $6)
+            GROUP BY d.id, o.id
+            ORDER BY rounds DESC, d.hash
+            LIMIT $7",
+            filtered = FILTERED_GAMES,
+            spawn_stats = SPAWN_STATS,
+        )),
+        filter
+    )
+    .bind::<Nullable<BigInt>, _>(hash)
+    .bind::<Nullable<BigInt>, _>(owner_user_id)
+    .bind::<BigInt, _>(limit)
+    .load(conn)
+}
+
+/// Names the design with the hash, or removes the name. Returns whether the design is known.
+pub fn label_design(conn: &DbConnection, hash: i64, label: Option<&str>) -> QueryResult<bool> {
+    let updated = diesel::update(designs::table.filter(designs::hash.eq(hash)))
+        .set(designs::label.eq(label))
+        .execute(conn)?;
+    Ok(updated > 0)
+}
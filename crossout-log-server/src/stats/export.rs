@@ -0,0 +1,296 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::{BigInt, Float, Integer, Nullable, Timestamptz, Varchar};
+use flagset::FlagSet;
+
+use crossout_log_common::filter::Filter;
+use crossout_log_common::log::{Assist, Damage, DamageFlag, Entry, Kill, Payload};
+
+use crate::db::DbConnection;
+use crate::stats::{bind_game_filter, GameFilter, FILTERED_GAMES};
+
+#[derive(QueryableByName)]
+struct DamageRow {
+    #[sql_type = "Integer"]
+    id: i32,
+    #[sql_type = "Timestamptz"]
+    time_stamp: DateTime<Utc>,
+    #[sql_type = "Varchar"]
+    victim: String,
+    #[sql_type = "Varchar"]
+    attacker: String,
+    #[sql_type = "Varchar"]
+    weapon: String,
+    #[sql_type = "Float"]
+    value: f32,
+    #[sql_type = "Integer"]
+    flags: i32,
+}
+
+#[derive(QueryableByName)]
+struct KillRow {
+    #[sql_type = "Integer"]
+    id: i32,
+    #[sql_type = "Timestamptz"]
+    time_stamp: DateTime<Utc>,
+    #[sql_type = "Varchar"]
+    victim: String,
+    #[sql_type = "Varchar"]
+    killer: String,
+}
+
+#[derive(QueryableByName)]
+struct AssistRow {
+    #[sql_type = "Integer"]
+    id: i32,
+    #[sql_type = "Integer"]
+    kill_id: i32,
+    #[sql_type = "Timestamptz"]
+    time_stamp: DateTime<Utc>,
+    #[sql_type = "Varchar"]
+    assistant: String,
+    #[sql_type = "Varchar"]
+    weapon: String,
+    #[sql_type = "Float"]
+    elapsed_sec: f32,
+    #[sql_type = "Float"]
+    damage_dealt: f32,
+    #[sql_type = "Integer"]
+    damage_flags: i32,
+}
+
+/// The number of rows of a kind read at once to apply a `where` filter.
+const PAGE_SIZE: i64 = 10_000;
+
+/// Restricts the rows of the rounds `r` to the filtered games, or to the game bound to `$5`.
+const EXPORTED_ROUNDS: &str = "r.game_id IN (SELECT id FROM filtered)
+    AND ($5::int4 IS NULL OR r.game_id = $5)";
+
+/// Rebuilds the damage, kill and assist entries of the games, or of the game with the id, which match the filter. Ordered by time, at most `limit` entries.
+///
+/// The rows are read page by page until `limit` entries of each kind match the filter, so only a page and the matching entries are held in memory. A filter should still be combined with a game or a time window, as all rows may have to be read.
+/// The nicknames are the current names of the players. Kills without a time stamp cannot be exported.
+pub fn export_entries(
+    conn: &DbConnection,
+    game_id: Option<i32>,
+    where_: Option<&Filter>,
+    limit: usize,
+    filter: &GameFilter,
+) -> QueryResult<Vec<Entry>> {
+    let damages = scan(
+        |page_size, after: Option<(DateTime<Utc>, i32)>| {
+            bind_game_filter!(
+                sql_query(format!(
+                    "WITH filtered AS ({})
+                    SELECT d.id, d.damage_ts AS time_stamp, pv.name AS victim, pa.name AS attacker,
+                        w.name AS weapon, d.value, d.flags
+                    FROM damages d
+                    JOIN rounds r ON r.id = d.round_id
+                    JOIN spawns sv ON sv.id = d.victim_id
+                    JOIN players pv ON pv.id = sv.player_id
+                    JOIN spawns sa ON sa.id = d.attacker_id
+                    JOIN players pa ON pa.id = sa.player_id
+                    JOIN weapons w ON w.id = d.weapon_id
+                    WHERE {}
+                    AND ($7::timestamptz IS NULL OR (d.damage_ts, d.id) > ($7, $8::int4))
+                    ORDER BY d.damage_ts, d.id
+                    LIMIT $6",
+                    FILTERED_GAMES, EXPORTED_ROUNDS
+                )),
+                filter
+            )
+            .bind::<Nullable<Integer>, _>(game_id)
+            .bind::<BigInt, _>(page_size)
+            .bind::<Nullable<Timestamptz>, _>(after.map(|(ts, _)| ts))
+            .bind::<Nullable<Integer>, _>(after.map(|(_, id)| id))
+            .load::<DamageRow>(conn)
+        },
+        |row| (row.time_stamp, row.id),
+        |row| {
+            entry(
+                row.time_stamp,
+                Damage {
+                    victim: row.victim,
+                    attacker: row.attacker,
+                    weapon: row.weapon,
+                    value: row.value,
+                    flags: FlagSet::<DamageFlag>::new_truncated(row.flags as u32),
+                }
+                .into(),
+            )
+        },
+        where_,
+        limit,
+    )?;
+    let kills = scan(
+        |page_size, after: Option<(DateTime<Utc>, i32)>| {
+            bind_game_filter!(
+                sql_query(format!(
+                    "WITH filtered AS ({})
+                    SELECT k.id, k.kill_ts AS time_stamp, pv.name AS victim, pk.name AS killer
+                    FROM kills k
+                    JOIN rounds r ON r.id = k.round_id
+                    JOIN spawns sv ON sv.id = k.victim_id
+                    JOIN players pv ON pv.id = sv.player_id
+                    JOIN spawns sk ON sk.id = k.killer_id
+                    JOIN players pk ON pk.id = sk.player_id
+                    WHERE k.kill_ts IS NOT NULL AND {}
+                    AND ($7::timestamptz IS NULL OR (k.kill_ts, k.id) > ($7, $8::int4))
+                    ORDER BY k.kill_ts, k.id
+                    LIMIT $6",
+                    FILTERED_GAMES, EXPORTED_ROUNDS
+                )),
+                filter
+            )
+            .bind::<Nullable<Integer>, _>(game_id)
+            .bind::<BigInt, _>(page_size)
+            .bind::<Nullable<Timestamptz>, _>(after.map(|(ts, _)| ts))
+            .bind::<Nullable<Integer>, _>(after.map(|(_, id)| id))
+            .load::<KillRow>(conn)
+        },
+        |row| (row.time_stamp, row.id),
+        |row| {
+            entry(
+                row.time_stamp,
+                Kill {
+                    victim: row.victim,
+                    killer: row.killer,
+                }
+                .into(),
+            )
+        },
+        where_,
+        limit,
+    )?;
+    let assists = scan(
+        |page_size, after: Option<(DateTime<Utc>, i32, i32)>| {
+            bind_game_filter!(
+                sql_query(format!(
+                    "WITH filtered AS ({})
+                    SELECT a.id, k.id AS kill_id, k.kill_ts AS time_stamp, p.name AS assistant,
+                        w.name AS weapon, a.elapsed_sec, a.damage_dealt, a.damage_flags
+                    FROM assists a
+                    JOIN kills k ON k.id = a.kill_id
+                    JOIN rounds r ON r.id = k.round_id
+                    JOIN spawns s ON s.id = a.assistant_id
+                    JOIN players p ON p.id = s.player_id
+                    JOIN weapons w ON w.id = a.weapon_id
+                    WHERE k.kill_ts IS NOT NULL AND {}
+                    AND ($7::timestamptz IS NULL
+                        OR (k.kill_ts, k.id, a.id) > ($7, $8::int4, $9::int4))
+                    ORDER BY k.kill_ts, k.id, a.id
+                    LIMIT $6",
+                    FILTERED_GAMES, EXPORTED_ROUNDS
+                )),
+                filter
+            )
+            .bind::<Nullable<Integer>, _>(game_id)
+            .bind::<BigInt, _>(page_size)
+            .bind::<Nullable<Timestamptz>, _>(after.map(|(ts, _, _)| ts))
+            .bind::<Nullable<Integer>, _>(after.map(|(_, kill_id, _)| kill_id))
+            .bind::<Nullable<Integer>, _>(after.map(|(_, _, id)| id))
+            .load::<AssistRow>(conn)
+        },
+        |row| (row.time_stamp, row.kill_id, row.id),
+        |row| {
+            entry(
+                row.time_stamp,
+                Assist {
+                    assistant: row.assistant,
+                    weapon: row.weapon,
+                    elapsed_sec: row.elapsed_sec,
+                    damage_dealt: row.damage_dealt,
+                    damage_flags: FlagSet::<DamageFlag>::new_truncated(row.damage_flags as u32),
+                }
+                .into(),
+            )
+        },
+        where_,
+        limit,
+    )?;
+
+    // the sort is stable, so the assists follow their kill as in the log
+    let mut entries: Vec<Entry> = damages.into_iter().chain(kills).chain(assists).collect();
+    entries.sort_by_key(|e| e.time_stamp);
+    entries.truncate(limit);
+    Ok(entries)
+}
+
+/// Reads the rows of a kind page by page, in the order of their `key`, until `limit` entries match the filter. Without a filter a single page of `limit` rows is read.
+fn scan<R, K: Copy>(
+    mut page: impl FnMut(i64, Option<K>) -> QueryResult<Vec<R>>,
+    key: impl Fn(&R) -> K,
+    entry: impl Fn(R) -> Entry,
+    where_: Option<&Filter>,
+    limit: usize,
+) -> QueryResult<Vec<Entry>> {
+    let page_size = match where_ {
+        Some(_) => PAGE_SIZE,
+        None => limit as i64,
+    };
+    let mut entries = Vec::new();
+    let mut after = None;
+    while entries.len() < limit {
+        let rows = page(page_size, after)?;
+        let last_page = (rows.len() as i64) < page_size;
+        after = rows.last().map(&key);
+        entries.extend(
+            rows.into_iter()
+                .map(&entry)
+                .filter(|e| where_.is_none_or(|f| f.matches(e))),
+        );
+        if last_page {
+            break;
+        }
+    }
+    entries.truncate(limit);
+    Ok(entries)
+}
+
+fn entry(time_stamp: DateTime<Utc>, message: Payload) -> Entry {
+    Entry {
+        time_stamp: time_stamp.naive_utc(),
+        message,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Duration, TimeZone};
+
+    use super::*;
+
+    #[test]
+    fn test_scan_pages_until_limit() {
+        let start = Utc.with_ymd_and_hms(2022, 6, 1, 20, 0, 0).unwrap();
+        let rows: Vec<(i32, String)> = (0..25_000)
+            .map(|id| (id, if id % 1000 == 999 { "Foo" } else { "Bar" }.to_string()))
+            .collect();
+        let mut pages = 0;
+        let page = |page_size: i64, after: Option<i32>| {
+            pages += 1;
+            let from = after.map_or(0, |id| id as usize + 1);
+            let to = (from + page_size as usize).min(rows.len());
+            Ok(rows[from..to].to_vec())
+        };
+        let entry = |(id, attacker): (i32, String)| {
+            let damage = Damage {
+                victim: "Baz".to_string(),
+                attacker,
+                weapon: "Gun".to_string(),
+                value: 1.0,
+                flags: FlagSet::default(),
+            };
+            super::entry(start + Duration::seconds(id as i64), damage.into())
+        };
+        let filter: Filter = "attacker == Foo".parse().unwrap();
+        let entries = scan(page, |row| row.0, entry, Some(&filter), 12).unwrap();
+        assert_eq!(entries.len(), 12);
+        assert_eq!(
+            entries[11].time_stamp,
+            (start + Duration::seconds(11_999)).naive_utc()
+        );
+        assert_eq!(pages, 2);
+    }
+}
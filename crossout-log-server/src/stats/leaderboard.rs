@@ -0,0 +1,112 @@
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::{BigInt, Bool, Double, Nullable, Varchar};
+use serde::{Deserialize, Serialize};
+
+use crate::db::DbConnection;
+use crate::stats::{bind_game_filter, GameFilter, FILTERED_GAMES, SPAWN_STATS};
+
+/// The default number of players in a leaderboard.
+const DEFAULT_LIMIT: i64 = 100;
+/// The maximum number of players in a leaderboard.
+const MAX_LIMIT: i64 = 1000;
+
+/// The statistic by which players are ranked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    Kills,
+    /// Kills per death.
+    #[serde(rename = "kd")]
+    KillDeath,
+    Assists,
+    /// Damage dealt to other players.
+    Damage,
+    DamagePerRound,
+    /// The sum of all points scored.
+    Score,
+    /// The share of rounds won.
+    WinRate,
+    /// The sum of all stripe values.
+    Stripes,
+}
+
+impl Metric {
+    /// The aggregate over the per spawn statistics.
+    fn aggregate(self) -> &'static str {
+        match self {
+            Metric::Kills => "sum(stats.kills)",
+            Metric::KillDeath => "sum(stats.kills)::double precision / greatest(sum(stats.deaths), 1)",
+            Metric::Assists => "sum(stats.assists)",
+            Metric::Damage => "sum(stats.damage)",
+            Metric::DamagePerRound => "sum(stats.damage) / count(DISTINCT stats.round_id)",
+            Metric::Score => "sum(stats.score)",
+            Metric::WinRate => "avg(stats.won)",
+            Metric::Stripes => "sum(stats.stripes)",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct LeaderboardFilter {
+    #[serde(flatten)]
+    pub games: GameFilter,
+    /// Only rounds in which the team of the player had this many players.
+    pub team_size: Option<i64>,
+    #[serde(default)]
+    pub exclude_bots: bool,
+    /// The minimum number of games a player must have played to be ranked.
+    pub min_games: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Clone, QueryableByName, Serialize)]
+pub struct LeaderboardRow {
+    #[sql_type = "BigInt"]
+    pub user_id: i64,
+    #[sql_type = "Varchar"]
+    pub name: String,
+    #[sql_type = "BigInt"]
+    pub games: i64,
+    #[sql_type = "BigInt"]
+    pub rounds: i64,
+    #[sql_type = "Double"]
+    pub value: f64,
+}
+
+/// Ranks the players by the metric, best first. Bots share the user id 0, so players are ranked by their row, as the bots are told apart by name.
+pub fn leaderboard(
+    conn: &DbConnection,
+    metric: Metric,
+    filter: &LeaderboardFilter,
+) -> QueryResult<Vec<LeaderboardRow>> {
+    let query = format!(
+        "WITH filtered AS ({filtered}),
+        stats AS (
+            {spawn_stats}
+            AND r.game_id IN (SELECT id FROM filtered)
+            AND ($5::bigint IS NULL OR $5 = (SELECT count(*) FROM spawns t
+                WHERE t.round_id = s.round_id AND t.team = s.team))
+            AND (NOT $6 OR s.bot = 0)
+        )
+        SELECT p.user_id, p.name,
+            count(DISTINCT stats.game_id) AS games,
+            count(DISTINCT stats.round_id) AS rounds,
+            ({aggregate})::double precision AS value
+        FROM stats
+        JOIN players p ON p.id = stats.player_id
+        GROUP BY p.id, p.user_id
+        HAVING count(DISTINCT stats.game_id) >= $7
+        ORDER BY value DESC
+        LIMIT $8",
+        filtered = FILTERED_GAMES,
+        spawn_stats = SPAWN_STATS,
+        aggregate = metric.aggregate(),
+    );
+    bind_game_filter!(sql_query(query), filter.games)
+        .bind::<Nullable<BigInt>, _>(filter.team_size)
+        .bind::<Bool, _>(filter.exclude_bots)
+        .bind::<BigInt, _>(filter.min_games.unwrap_or(1))
+        .bind::<BigInt, _>(filter.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT))
+        .load(conn)
+}
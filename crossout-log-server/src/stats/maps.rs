@@ -0,0 +1,166 @@
+use std::collections::{BTreeMap, HashMap};
+
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::{BigInt, Double, Integer, Nullable, SmallInt, Varchar};
+use serde::Serialize;
+
+use crossout_log_common::log::{FinishReason, FinishReasonMapping, WinReason, WinReasonMapping};
+
+use crate::db::DbConnection;
+use crate::stats::{bind_game_filter, GameFilter, FILTERED_GAMES};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MapStats {
+    pub id: i32,
+    pub name: String,
+    pub games: i64,
+    pub rounds: i64,
+    /// The average battle time of a round in seconds.
+    pub average_duration: f64,
+    /// The share of rounds won by each team number.
+    pub win_rate_by_team: BTreeMap<i16, f64>,
+    /// The number of rounds by finish reason.
+    pub finish_reasons: BTreeMap<String, i64>,
+    /// The number of rounds by win reason.
+    pub win_reasons: BTreeMap<String, i64>,
+    pub average_kills: f64,
+}
+
+#[derive(QueryableByName)]
+struct MapRow {
+    #[sql_type = "Integer"]
+    id: i32,
+    #[sql_type = "Varchar"]
+    name: String,
+    #[sql_type = "BigInt"]
+    games: i64,
+    #[sql_type = "BigInt"]
+    rounds: i64,
+    #[sql_type = "Double"]
+    average_duration: f64,
+    #[sql_type = "BigInt"]
+    kills: i64,
+}
+
+#[derive(QueryableByName)]
+struct TeamRow {
+    #[sql_type = "Integer"]
+    map_id: i32,
+    #[sql_type = "SmallInt"]
+    team: i16,
+    #[sql_type = "BigInt"]
+    rounds: i64,
+}
+
+#[derive(QueryableByName)]
+struct ReasonRow {
+    #[sql_type = "Integer"]
+    map_id: i32,
+    #[sql_type = "FinishReasonMapping"]
+    finish_reason: FinishReason,
+    #[sql_type = "WinReasonMapping"]
+    win_reason: WinReason,
+    #[sql_type = "BigInt"]
+    rounds: i64,
+}
+
+/// Aggregates the rounds played on all maps, or on the map with the id. Ordered by the number of games, highest first.
+pub fn map_stats(
+    conn: &DbConnection,
+    map_id: Option<i32>,
+    filter: &GameFilter,
+) -> QueryResult<Vec<MapStats>> {
+    let maps: Vec<MapRow> = bind_game_filter!(
+        sql_query(format!(
+            "WITH filtered AS ({})
+            SELECT m.id, m.name, count(DISTINCT g.id) AS games, count(r.id) AS rounds,
+                coalesce(avg(r.duration), 0)::double precision AS average_duration,
+                (SELECT count(*) FROM kills k JOIN rounds kr ON kr.id = k.round_id
+                    JOIN games kg ON kg.id = kr.game_id
+                    WHERE kg.map_id = m.id AND kg.id IN (SELECT id FROM filtered)) AS kills
+            FROM maps m
+            JOIN games g ON g.map_id = m.id
+            JOIN rounds r ON r.game_id = g.id
+            WHERE g.id IN (SELECT id FROM filtered)
+            AND ($5::integer IS NULL OR m.id = $5)
+            GROUP BY m.id, m.name
+            ORDER BY games DESC",
+            FILTERED_GAMES
+        )),
+        filter
+    )
+    .bind::<Nullable<Integer>, _>(map_id)
+    .load(conn)?;
+    let teams: Vec<TeamRow> = bind_game_filter!(
+        sql_query(format!(
+            "WITH filtered AS ({})
+            SELECT g.map_id, r.winning_team AS team, count(*) AS rounds
+            FROM rounds r
+            JOIN games g ON g.id = r.game_id
+            WHERE g.id IN (SELECT id FROM filtered)
+            AND ($5::integer IS NULL OR g.map_id = $5)
+            GROUP BY g.map_id, r.winning_team",
+            FILTERED_GAMES
+        )),
+        filter
+    )
+    .bind::<Nullable<Integer>, _>(map_id)
+    .load(conn)?;
+    let reasons: Vec<ReasonRow> = bind_game_filter!(
+        sql_query(format!(
+            "WITH filtered AS ({})
+            SELECT g.map_id, r.finish_reason, r.win_reason, count(*) AS rounds
+            FROM rounds r
+            JOIN games g ON g.id = r.game_id
+            WHERE g.id IN (SELECT id FROM filtered)
+            AND ($5::integer IS NULL OR g.map_id = $5)
+            GROUP BY g.map_id, r.finish_reason, r.win_reason",
+            FILTERED_GAMES
+        )),
+        filter
+    )
+    .bind::<Nullable<Integer>, _>(map_id)
+    .load(conn)?;
+
+    let mut wins = HashMap::<_, BTreeMap<i16, f64>>::new();
+    for row in teams {
+        *wins.entry(row.map_id).or_default().entry(row.team).or_default() += row.rounds as f64;
+    }
+    let mut finish_reasons = HashMap::<_, BTreeMap<String, i64>>::new();
+    let mut win_reasons = HashMap::<_, BTreeMap<String, i64>>::new();
+    for row in reasons {
+        *finish_reasons
+            .entry(row.map_id)
+            .or_default()
+            .entry(row.finish_reason.to_string())
+            .or_default() += row.rounds;
+        *win_reasons
+            .entry(row.map_id)
+            .or_default()
+            .entry(row.win_reason.to_string())
+            .or_default() += row.rounds;
+    }
+    Ok(maps
+        .into_iter()
+        .map(|m| {
+            let rounds = m.rounds.max(1) as f64;
+            MapStats {
+                win_rate_by_team: wins
+                    .remove(&m.id)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|(team, won)| (team, won / rounds))
+                    .collect(),
+                finish_reasons: finish_reasons.remove(&m.id).unwrap_or_default(),
+                win_reasons: win_reasons.remove(&m.id).unwrap_or_default(),
+                average_kills: m.kills as f64 / rounds,
+                id: m.id,
+                name: m.name,
+                games: m.games,
+                rounds: m.rounds,
+                average_duration: m.average_duration,
+            }
+        })
+        .collect())
+}
@@ -0,0 +1,61 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+pub mod clanwars;
+pub mod designs;
+pub mod export;
+pub mod leaderboard;
+pub mod maps;
+pub mod parties;
+pub mod players;
+pub mod weapons;
+
+/// Restricts statistics to the games in a time window, on a map or of a game mode. Test drives are never included.
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct GameFilter {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub map: Option<String>,
+    pub game_mode: Option<String>,
+}
+
+/// Selects the ids of the games matching the [`GameFilter`] bound to `$1` through `$4` by [`bind_game_filter`].
+pub const FILTERED_GAMES: &str = "SELECT g.id FROM games g
+    JOIN maps m ON m.id = g.map_id
+    LEFT JOIN game_modes gm ON gm.id = g.game_mode_id
+    WHERE NOT g.test_drive
+    AND ($1::timestamptz IS NULL OR g.start_ts >= $1)
+    AND ($2::timestamptz IS NULL OR g.start_ts < $2)
+    AND ($3::varchar IS NULL OR m.name = $3)
+    AND ($4::varchar IS NULL OR gm.name = $4)";
+
+/// Selects the performance of the spawns `s` in the rounds `r`. Append the conditions to the `WHERE` clause.
+pub const SPAWN_STATS: &str = "SELECT s.id AS spawn_id, s.player_id, r.game_id, s.round_id,
+        s.team, s.party, s.design, s.bot,
+        (SELECT count(*) FROM kills k WHERE k.killer_id = s.id AND k.victim_id <> s.id) AS kills,
+        (SELECT count(*) FROM kills k WHERE k.victim_id = s.id) AS deaths,
+        (SELECT count(*) FROM assists a WHERE a.assistant_id = s.id) AS assists,
+        (SELECT coalesce(sum(d.value), 0) FROM damages d
+            WHERE d.attacker_id = s.id AND d.victim_id <> s.id)::double precision AS damage,
+        (SELECT coalesce(sum(sc.value), 0) FROM scores sc
+            WHERE sc.spawn_id = s.id)::double precision AS score,
+        (SELECT coalesce(sum(st.value), 0) FROM stripes st
+            WHERE st.spawn_id = s.id)::double precision AS stripes,
+        CASE WHEN s.team = r.winning_team THEN 1.0 ELSE 0.0 END AS won
+    FROM spawns s
+    JOIN rounds r ON r.id = s.round_id
+    WHERE TRUE";
+
+/// Binds the [`GameFilter`] to the parameters of [`FILTERED_GAMES`].
+macro_rules! bind_game_filter {
+    ($query:expr, $filter:expr) => {
+        $query
+            .bind::<diesel::sql_types::Nullable<diesel::sql_types::Timestamptz>, _>($filter.from)
+            .bind::<diesel::sql_types::Nullable<diesel::sql_types::Timestamptz>, _>($filter.to)
+            .bind::<diesel::sql_types::Nullable<diesel::sql_types::Varchar>, _>($filter.map.clone())
+            .bind::<diesel::sql_types::Nullable<diesel::sql_types::Varchar>, _>(
+                $filter.game_mode.clone(),
+            )
+    };
+}
+pub(crate) use bind_game_filter;
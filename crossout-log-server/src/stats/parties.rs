@@ -0,0 +1,162 @@
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::{Array, BigInt, Double, Nullable, Varchar};
+use serde::Serialize;
+
+use crate::db::DbConnection;
+use crate::stats::{bind_game_filter, GameFilter, FILTERED_GAMES, SPAWN_STATS};
+
+/// The number of squad compositions listed.
+const COMPOSITION_LIMIT: i64 = 20;
+
+/// Groups the spawn statistics of the filtered games by party. Players without a party (0) form a party of one.
+const SIZED_SPAWNS: &str = "stats AS (
+        {spawn_stats}
+        AND r.game_id IN (SELECT id FROM filtered)
+    ),
+    sized AS (
+        SELECT stats.*, p.user_id, p.name,
+            CASE WHEN stats.party = 0 THEN 1
+                ELSE count(*) OVER (PARTITION BY stats.round_id, stats.team, stats.party)
+            END AS party_size
+        FROM stats
+        JOIN players p ON p.id = stats.player_id
+    )";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PartyStats {
+    pub by_size: Vec<PartySize>,
+    pub compositions: Vec<Composition>,
+    pub matchups: Vec<PartyMatchup>,
+}
+
+/// The performance of the players in parties of a size. The size 1 are solo players.
+#[derive(Debug, Clone, QueryableByName, Serialize)]
+pub struct PartySize {
+    #[sql_type = "BigInt"]
+    pub size: i64,
+    #[sql_type = "BigInt"]
+    pub rounds: i64,
+    #[sql_type = "Double"]
+    pub win_rate: f64,
+    #[sql_type = "Double"]
+    pub average_kills: f64,
+    #[sql_type = "Double"]
+    pub average_damage: f64,
+    #[sql_type = "Double"]
+    pub average_score: f64,
+}
+
+/// A squad of players who queued together repeatedly.
+#[derive(Debug, Clone, QueryableByName, Serialize)]
+pub struct Composition {
+    #[sql_type = "Array<BigInt>"]
+    pub user_ids: Vec<i64>,
+    #[sql_type = "Array<Varchar>"]
+    pub names: Vec<String>,
+    #[sql_type = "BigInt"]
+    pub rounds: i64,
+    #[sql_type = "Double"]
+    pub win_rate: f64,
+}
+
+/// The outcome of the rounds by the largest party of the team and of the opponents, a measure of matchmaking fairness.
+#[derive(Debug, Clone, QueryableByName, Serialize)]
+pub struct PartyMatchup {
+    #[sql_type = "BigInt"]
+    pub largest_party: i64,
+    #[sql_type = "BigInt"]
+    pub largest_enemy_party: i64,
+    #[sql_type = "BigInt"]
+    pub rounds: i64,
+    #[sql_type = "Double"]
+    pub win_rate: f64,
+}
+
+/// Analyses the parties of all players, or only the parties of the player with the user id.
+pub fn party_stats(
+    conn: &DbConnection,
+    user_id: Option<i64>,
+    filter: &GameFilter,
+) -> QueryResult<PartyStats> {
+    let sized = SIZED_SPAWNS.replace("{spawn_stats}", SPAWN_STATS);
+    let by_size = bind_game_filter!(
+        sql_query(format!(
+            "WITH filtered AS ({filtered}), {sized}
+            SELECT party_size AS size, count(*) AS rounds,
+                avg(won)::double precision AS win_rate,
+                avg(kills)::double precision AS average_kills,
+                avg(damage) AS average_damage,
+                avg(score) AS average_score
+            FROM sized
+            WHERE bot = 0 AND ($5::bigint IS NULL OR user_id = $5)
+            GROUP BY party_size
+            ORDER BY party_size",
+            filtered = FILTERED_GAMES,
+            sized = sized,
+        )),
+        filter
+    )
+    .bind::<Nullable<BigInt>, _>(user_id)
+    .load(conn)?;
+
+    let compositions = bind_game_filter!(
+        sql_query(format!(
+            "WITH filtered AS ({filtered}), {sized},
+            squads AS (
+                SELECT array_agg(user_id ORDER BY user_id) AS user_ids,
+                    array_agg(name ORDER BY user_id) AS names,
+                    max(won) AS won
+                FROM sized
+                WHERE party <> 0
+                GROUP BY round_id, team, party
+                HAVING count(*) > 1
+            )
+            SELECT user_ids, names, count(*) AS rounds, avg(won)::double precision AS win_rate
+            FROM squads
+            WHERE $5::bigint IS NULL OR $5 = ANY(user_ids)
+            GROUP BY user_ids, names
+            ORDER BY rounds DESC
+            LIMIT $6",
+            filtered = FILTERED_GAMES,
+            sized = sized,
+        )),
+        filter
+    )
+    .bind::<Nullable<BigInt>, _>(user_id)
+    .bind::<BigInt, _>(COMPOSITION_LIMIT)
+    .load(conn)?;
+
+    let matchups = bind_game_filter!(
+        sql_query(format!(
+            "WITH filtered AS ({filtered}), {sized},
+            teams AS (
+                SELECT round_id, team, max(party_size) AS largest, max(won) AS won,
+                    bool_or(user_id = $5) AS member
+                FROM sized
+                GROUP BY round_id, team
+            )
+            SELECT t.largest AS largest_party, e.largest AS largest_enemy_party,
+                count(*) AS rounds, avg(t.won)::double precision AS win_rate
+            FROM teams t
+            JOIN LATERAL (
+                SELECT max(o.largest) AS largest FROM teams o
+                WHERE o.round_id = t.round_id AND o.team <> t.team
+            ) e ON e.largest IS NOT NULL
+            WHERE $5::bigint IS NULL OR t.member
+            GROUP BY t.largest, e.largest
+            ORDER BY t.largest, e.largest",
+            filtered = FILTERED_GAMES,
+            sized = sized,
+        )),
+        filter
+    )
+    .bind::<Nullable<BigInt>, _>(user_id)
+    .load(conn)?;
+
+    Ok(PartyStats {
+        by_size,
+        compositions,
+        matchups,
+    })
+}
@@ -0,0 +1,319 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::{BigInt, Double, Integer, SmallInt, Timestamptz, Varchar};
+use serde::Serialize;
+
+use crate::db::DbConnection;
+use crate::stats::{bind_game_filter, GameFilter, FILTERED_GAMES, SPAWN_STATS};
+
+/// The number of weapons, designs and party mates in a profile.
+const TOP_LIMIT: i64 = 10;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlayerProfile {
+    pub user_id: i64,
+    /// The most recently seen nickname.
+    pub name: String,
+    pub career: CareerStats,
+    pub recent_games: Vec<RecentGame>,
+    pub weapons: Vec<WeaponUsage>,
+    pub designs: Vec<DesignUsage>,
+    pub party_mates: Vec<PartyMate>,
+    pub names: Vec<NameHistory>,
+}
+
+#[derive(Debug, Clone, QueryableByName, Serialize)]
+pub struct CareerStats {
+    #[sql_type = "BigInt"]
+    pub games: i64,
+    #[sql_type = "BigInt"]
+    pub rounds: i64,
+    #[sql_type = "BigInt"]
+    pub kills: i64,
+    #[sql_type = "BigInt"]
+    pub deaths: i64,
+    #[sql_type = "BigInt"]
+    pub assists: i64,
+    #[sql_type = "Double"]
+    pub damage: f64,
+    #[sql_type = "Double"]
+    pub score: f64,
+    #[sql_type = "Double"]
+    pub stripes: f64,
+    /// The share of rounds won.
+    #[sql_type = "Double"]
+    pub win_rate: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecentGame {
+    pub id: i32,
+    pub start_ts: DateTime<Utc>,
+    pub map: String,
+    pub game_mode: Option<String>,
+    pub rounds: Vec<RoundPerformance>,
+}
+
+#[derive(Debug, Clone, QueryableByName, Serialize)]
+pub struct RoundPerformance {
+    #[serde(skip)]
+    #[sql_type = "Integer"]
+    game_id: i32,
+    #[sql_type = "SmallInt"]
+    pub round_no: i16,
+    #[sql_type = "SmallInt"]
+    pub team: i16,
+    #[sql_type = "BigInt"]
+    pub design: i64,
+    #[sql_type = "BigInt"]
+    pub kills: i64,
+    #[sql_type = "BigInt"]
+    pub deaths: i64,
+    #[sql_type = "BigInt"]
+    pub assists: i64,
+    #[sql_type = "Double"]
+    pub damage: f64,
+    #[sql_type = "Double"]
+    pub score: f64,
+    #[sql_type = "Double"]
+    pub won: f64,
+}
+
+#[derive(QueryableByName)]
+struct GameRow {
+    #[sql_type = "Integer"]
+    id: i32,
+    #[sql_type = "Timestamptz"]
+    start_ts: DateTime<Utc>,
+    #[sql_type = "Varchar"]
+    map: String,
+    #[sql_type = "diesel::sql_types::Nullable<Varchar>"]
+    game_mode: Option<String>,
+}
+
+#[derive(Debug, Clone, QueryableByName, Serialize)]
+pub struct WeaponUsage {
+    #[sql_type = "Varchar"]
+    pub name: String,
+    #[sql_type = "BigInt"]
+    pub hits: i64,
+    #[sql_type = "Double"]
+    pub damage: f64,
+    #[sql_type = "BigInt"]
+    pub rounds: i64,
+}
+
+#[derive(Debug, Clone, QueryableByName, Serialize)]
+pub struct DesignUsage {
+    /// The `mmHash` of the vehicle.
+    #[sql_type = "BigInt"]
+    pub design: i64,
+    #[sql_type = "BigInt"]
+    pub rounds: i64,
+    #[sql_type = "Double"]
+    pub win_rate: f64,
+}
+
+#[derive(Debug, Clone, QueryableByName, Serialize)]
+pub struct PartyMate {
+    #[sql_type = "BigInt"]
+    pub user_id: i64,
+    #[sql_type = "Varchar"]
+    pub name: String,
+    #[sql_type = "BigInt"]
+    pub rounds: i64,
+    /// The share of rounds won together.
+    #[sql_type = "Double"]
+    pub win_rate: f64,
+}
+
+#[derive(Debug, Clone, QueryableByName, Serialize)]
+pub struct NameHistory {
+    #[sql_type = "Varchar"]
+    pub name: String,
+    #[sql_type = "Timestamptz"]
+    pub first_seen: DateTime<Utc>,
+    #[sql_type = "Timestamptz"]
+    pub last_seen: DateTime<Utc>,
+}
+
+/// Collects the statistics of the player with the user id, or `None` if the player was never seen. At most `recent` games are listed, newest first.
+pub fn player_profile(
+    conn: &DbConnection,
+    user_id: i64,
+    recent: i64,
+    filter: &GameFilter,
+) -> QueryResult<Option<PlayerProfile>> {
+    let names: Vec<NameHistory> = sql_query(
+        "SELECT pn.name, pn.first_seen, pn.last_seen
+        FROM player_names pn
+        JOIN players p ON p.id = pn.player_id
+        WHERE p.user_id = $1
+        ORDER BY pn.last_seen DESC",
+    )
+    .bind::<BigInt, _>(user_id)
+    .load(conn)?;
+    let name = match names.first() {
+        Some(latest) => latest.name.clone(),
+        None => return Ok(None),
+    };
+
+    let career = bind_game_filter!(
+        sql_query(format!(
+            "WITH filtered AS ({filtered}),
+            stats AS (
+                {spawn_stats}
+                AND r.game_id IN (SELECT id FROM filtered)
+                AND s.player_id IN (SELECT id FROM players WHERE user_id = $5)
+            )
+            SELECT count(DISTINCT game_id) AS games, count(DISTINCT round_id) AS rounds,
+                coalesce(sum(kills), 0)::bigint AS kills,
+                coalesce(sum(deaths), 0)::bigint AS deaths,
+                coalesce(sum(assists), 0)::bigint AS assists,
+                coalesce(sum(damage), 0) AS damage,
+                coalesce(sum(score), 0) AS score,
+                coalesce(sum(stripes), 0) AS stripes,
+                coalesce(avg(won), 0)::double precision AS win_rate
+            FROM stats",
+            filtered = FILTERED_GAMES,
+            spawn_stats = SPAWN_STATS,
+        )),
+        filter
+    )
+    .bind::<BigInt, _>(user_id)
+    .get_result(conn)?;
+
+    let games: Vec<GameRow> = bind_game_filter!(
+        sql_query(format!(
+            "WITH filtered AS ({})
+            SELECT g.id, g.start_ts, m.name AS map, gm.name AS game_mode
+            FROM games g
+            JOIN maps m ON m.id = g.map_id
+            LEFT JOIN game_modes gm ON gm.id = g.game_mode_id
+            WHERE g.id IN (SELECT id FROM filtered)
+            AND EXISTS (SELECT 1 FROM spawns s
+                JOIN rounds r ON r.id = s.round_id
+                JOIN players p ON p.id = s.player_id
+                WHERE r.game_id = g.id AND p.user_id = $5)
+            ORDER BY g.start_ts DESC
+            LIMIT $6",
+            FILTERED_GAMES
+        )),
+        filter
+    )
+    .bind::<BigInt, _>(user_id)
+    .bind::<BigInt, _>(recent)
+    .load(conn)?;
+    let game_ids: Vec<i32> = games.iter().map(|g| g.id).collect();
+    let rounds: Vec<RoundPerformance> = sql_query(format!(
+        "SELECT stats.game_id, r.round_no, stats.team, stats.design, stats.kills, stats.deaths,
+            stats.assists, stats.damage, stats.score, stats.won::double precision AS won
+        FROM ({}
+            AND r.game_id = ANY($1)
+            AND s.player_id IN (SELECT id FROM players WHERE user_id = $2)) stats
+        JOIN rounds r ON r.id = stats.round_id
+        ORDER BY r.round_no",
+        SPAWN_STATS
+    ))
+    .bind::<diesel::sql_types::Array<Integer>, _>(&game_ids)
+    .bind::<BigInt, _>(user_id)
+    .load(conn)?;
+    let mut by_game = BTreeMap::<_, Vec<_>>::new();
+    for round in rounds {
+        by_game.entry(round.game_id).or_default().push(round);
+    }
+    let recent_games = games
+        .into_iter()
+        .map(|g| RecentGame {
+            rounds: by_game.remove(&g.id).unwrap_or_default(),
+            id: g.id,
+            start_ts: g.start_ts,
+            map: g.map,
+            game_mode: g.game_mode,
+        })
+        .collect();
+
+    let weapons = bind_game_filter!(
+        sql_query(format!(
+            "WITH filtered AS ({})
+            SELECT w.name, count(*) AS hits, sum(d.value)::double precision AS damage,
+                count(DISTINCT d.round_id) AS rounds
+            FROM damages d
+            JOIN weapons w ON w.id = d.weapon_id
+            JOIN spawns s ON s.id = d.attacker_id
+            JOIN players p ON p.id = s.player_id
+            JOIN rounds r ON r.id = d.round_id
+            WHERE r.game_id IN (SELECT id FROM filtered)
+            AND p.user_id = $5 AND d.victim_id <> d.attacker_id
+            GROUP BY w.name
+            ORDER BY damage DESC
+            LIMIT $6",
+            FILTERED_GAMES
+        )),
+        filter
+    )
+    .bind::<BigInt, _>(user_id)
+    .bind::<BigInt, _>(TOP_LIMIT)
+    .load(conn)?;
+
+    let designs = bind_game_filter!(
+        sql_query(format!(
+            "WITH filtered AS ({})
+            SELECT s.design, count(*) AS rounds,
+                avg(CASE WHEN s.team = r.winning_team THEN 1.0 ELSE 0.0 END)::double precision
+                    AS win_rate
+            FROM spawns s
+            JOIN players p ON p.id = s.player_id
+            JOIN rounds r ON r.id = s.round_id
+            WHERE r.game_id IN (SELECT id FROM filtered)
+            AND p.user_id = $5
+            GROUP BY s.design
+            ORDER BY rounds DESC
+            LIMIT $6",
+            FILTERED_GAMES
+        )),
+        filter
+    )
+    .bind::<BigInt, _>(user_id)
+    .bind::<BigInt, _>(TOP_LIMIT)
+    .load(conn)?;
+
+    let party_mates = bind_game_filter!(
+        sql_query(format!(
+            "WITH filtered AS ({})
+            SELECT mp.user_id, max(mp.name) AS name, count(*) AS rounds,
+                avg(CASE WHEN s.team = r.winning_team THEN 1.0 ELSE 0.0 END)::double precision
+                    AS win_rate
+            FROM spawns s
+            JOIN players p ON p.id = s.player_id
+            JOIN rounds r ON r.id = s.round_id
+            JOIN spawns m ON m.round_id = s.round_id AND m.team = s.team
+                AND m.party = s.party AND m.id <> s.id
+            JOIN players mp ON mp.id = m.player_id
+            WHERE r.game_id IN (SELECT id FROM filtered)
+            AND p.user_id = $5 AND s.party <> 0 AND mp.user_id <> $5
+            GROUP BY mp.user_id
+            ORDER BY rounds DESC
+            LIMIT $6",
+            FILTERED_GAMES
+        )),
+        filter
+    )
+    .bind::<BigInt, _>(user_id)
+    .bind::<BigInt, _>(TOP_LIMIT)
+    .load(conn)?;
+
+    Ok(Some(PlayerProfile {
+        user_id,
+        name,
+        career,
+        recent_games,
+        weapons,
+        designs,
+        party_mates,
+        names,
+    }))
+}
@@ -0,0 +1,179 @@
+use std::collections::{BTreeMap, HashMap};
+
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::{BigInt, Double, Integer, Nullable, Varchar};
+use flagset::FlagSet;
+use serde::Serialize;
+
+use crossout_log_common::log::DamageFlag;
+
+use crate::db::DbConnection;
+use crate::stats::{bind_game_filter, GameFilter, FILTERED_GAMES};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WeaponStats {
+    pub name: String,
+    pub hits: i64,
+    pub damage: f64,
+    /// The average damage per hit.
+    pub average_damage: f64,
+    /// The share of the damage dealt with each `DamageFlag`. A hit usually has multiple flags, so the shares do not add up to one.
+    pub damage_share_by_flag: BTreeMap<String, f64>,
+    /// The number of kills where the weapon dealt the finishing blow.
+    pub kills: i64,
+    pub assists: i64,
+    /// The number of games in which the weapon dealt damage.
+    pub games: i64,
+    /// The share of games in which the weapon dealt damage.
+    pub usage_rate: f64,
+}
+
+#[derive(QueryableByName)]
+struct WeaponRow {
+    #[sql_type = "Varchar"]
+    name: String,
+    #[sql_type = "BigInt"]
+    hits: i64,
+    #[sql_type = "Double"]
+    damage: f64,
+    #[sql_type = "BigInt"]
+    games: i64,
+}
+
+#[derive(QueryableByName)]
+struct FlagsRow {
+    #[sql_type = "Varchar"]
+    name: String,
+    #[sql_type = "Integer"]
+    flags: i32,
+    #[sql_type = "Double"]
+    damage: f64,
+}
+
+#[derive(QueryableByName)]
+struct CountRow {
+    #[sql_type = "Varchar"]
+    name: String,
+    #[sql_type = "BigInt"]
+    count: i64,
+}
+
+#[derive(QueryableByName)]
+struct TotalRow {
+    #[sql_type = "BigInt"]
+    count: i64,
+}
+
+/// Aggregates the damage, kills and assists of all weapons, or of the weapon with the name. Ordered by damage dealt, highest first. Self-damage is not counted.
+pub fn weapon_stats(
+    conn: &DbConnection,
+    name: Option<&str>,
+    filter: &GameFilter,
+) -> QueryResult<Vec<WeaponStats>> {
+    let weapons: Vec<WeaponRow> = bind_game_filter!(
+        sql_query(format!(
+            "WITH filtered AS ({})
+            SELECT w.name, count(*) AS hits, sum(d.value)::double precision AS damage,
+                count(DISTINCT r.game_id) AS games
+            FROM damages d
+            JOIN weapons w ON w.id = d.weapon_id
+            JOIN rounds r ON r.id = d.round_id
+            WHERE r.game_id IN (SELECT id FROM filtered)
+            AND d.victim_id <> d.attacker_id
+            AND ($5::varchar IS NULL OR w.name = $5)
+            GROUP BY w.name
+            ORDER BY damage DESC",
+            FILTERED_GAMES
+        )),
+        filter
+    )
+    .bind::<Nullable<Varchar>, _>(name)
+    .load(conn)?;
+    let flags: Vec<FlagsRow> = bind_game_filter!(
+        sql_query(format!(
+            "WITH filtered AS ({})
+            SELECT w.name, d.flags, sum(d.value)::double precision AS damage
+            FROM damages d
+            JOIN weapons w ON w.id = d.weapon_id
+            JOIN rounds r ON r.id = d.round_id
+            WHERE r.game_id IN (SELECT id FROM filtered)
+            AND d.victim_id <> d.attacker_id
+            AND ($5::varchar IS NULL OR w.name = $5)
+            GROUP BY w.name, d.flags",
+            FILTERED_GAMES
+        )),
+        filter
+    )
+    .bind::<Nullable<Varchar>, _>(name)
+    .load(conn)?;
+    let kills = count_by_weapon(conn, "kills", name, filter)?;
+    let assists = count_by_weapon(conn, "assists", name, filter)?;
+    let total_games: TotalRow = bind_game_filter!(
+        sql_query(format!(
+            "WITH filtered AS ({}) SELECT count(*) AS count FROM filtered",
+            FILTERED_GAMES
+        )),
+        filter
+    )
+    .get_result(conn)?;
+
+    let mut damage_by_flag = HashMap::<_, BTreeMap<String, f64>>::new();
+    for row in flags {
+        let by_flag = damage_by_flag.entry(row.name).or_default();
+        for flag in FlagSet::<DamageFlag>::new_truncated(row.flags as u32) {
+            *by_flag.entry(flag.to_string()).or_default() += row.damage;
+        }
+    }
+    Ok(weapons
+        .into_iter()
+        .map(|w| WeaponStats {
+            average_damage: w.damage / w.hits.max(1) as f64,
+            damage_share_by_flag: damage_by_flag
+                .remove(&w.name)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(flag, damage)| (flag, damage / w.damage.max(f64::EPSILON)))
+                .collect(),
+            kills: kills.get(&w.name).copied().unwrap_or_default(),
+            assists: assists.get(&w.name).copied().unwrap_or_default(),
+            usage_rate: w.games as f64 / total_games.count.max(1) as f64,
+            name: w.name,
+            hits: w.hits,
+            damage: w.damage,
+            games: w.games,
+        })
+        .collect())
+}
+
+/// Counts the rows of the table with a `weapon_id` and `round_id` or `kill_id` column by weapon name.
+fn count_by_weapon(
+    conn: &DbConnection,
+    table: &str,
+    name: Option<&str>,
+    filter: &GameFilter,
+) -> QueryResult<HashMap<String, i64>> {
+    let round = match table {
+        "assists" => "JOIN kills k ON k.id = t.kill_id JOIN rounds r ON r.id = k.round_id",
+        _ => "JOIN rounds r ON r.id = t.round_id",
+    };
+    let rows: Vec<CountRow> = bind_game_filter!(
+        sql_query(format!(
+            "WITH filtered AS ({filtered})
+            SELECT w.name, count(*) AS count
+            FROM {table} t
+            JOIN weapons w ON w.id = t.weapon_id
+            {round}
+            WHERE r.game_id IN (SELECT id FROM filtered)
+            AND ($5::varchar IS NULL OR w.name = $5)
+            GROUP BY w.name",
+            filtered = FILTERED_GAMES,
+            table = table,
+            round = round,
+        )),
+        filter
+    )
+    .bind::<Nullable<Varchar>, _>(name)
+    .load(conn)?;
+    Ok(rows.into_iter().map(|r| (r.name, r.count)).collect())
+}
@@ -0,0 +1,29 @@
+//! Shared by the tests against the PostgreSQL database at `DATABASE_URL`, run with `cargo test -- --ignored`.
+//! The migrations and the test data are applied in a transaction which is never committed.
+use std::env;
+
+use chrono::NaiveDate;
+use diesel::{Connection, PgConnection};
+
+use crossout_log_common::game::{assemble_games, Game};
+use crossout_log_common::log::parse_entry;
+
+embed_migrations!("./migrations");
+
+pub fn connect() -> PgConnection {
+    let url = env::var("DATABASE_URL").expect("DATABASE_URL is not set");
+    let conn = PgConnection::establish(&url).expect("Failed to connect to the database");
+    conn.begin_test_transaction().unwrap();
+    embedded_migrations::run(&conn).expect("Failed to apply migrations");
+    conn
+}
+
+/// Parses the lines of the log, dated 2022-06-01, and groups the entries into games. The indentation of the lines is ignored.
+pub fn games(log: &str) -> Vec<Game> {
+    let date = NaiveDate::from_ymd_opt(2022, 6, 1).unwrap();
+    let entries = log
+        .lines()
+        .map(|line| parse_entry::<()>(date)(line.trim()).unwrap().1)
+        .collect();
+    assemble_games(entries)
+}
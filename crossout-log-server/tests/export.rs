@@ -0,0 +1,50 @@
+#[macro_use]
+extern crate diesel_migrations;
+
+mod common;
+
+use crossout_log_common::filter::Filter;
+use crossout_log_common::log::Payload;
+use crossout_log_server::ingest::ingest_games;
+use crossout_log_server::stats::export::export_entries;
+use crossout_log_server::stats::GameFilter;
+
+#[test]
+#[ignore = "requires a PostgreSQL database at DATABASE_URL"]
+fn test_export_filtered_entries() {
+    let conn = common::connect();
+    let games = common::games(
+        "20:00:00.000| ====== starting level 3: 'levels/maps/bad_rock' pvp ======
+        20:00:05.000| ===== Gameplay 'Pvp' started, map 'bad_rock' ======
+        20:00:05.000| player  0, uid 11, party 1, nickname: Foo, team: 1, bot: 0, ur: 5, mmHash: ab
+        20:00:05.000| player  1, uid 12, party 2, nickname: Bar, team: 2, bot: 0, ur: 6, mmHash: cd
+        20:00:30.000| Damage. Victim: Bar, attacker: Foo, weapon 'Gun', damage: 30.0 DMG_DIRECT
+        20:00:31.000| Damage. Victim: Foo, attacker: Bar, weapon 'Gun', damage: 10.0 DMG_DIRECT
+        20:00:32.000| Damage. Victim: Bar, attacker: Foo, weapon 'Gun', damage: 20.0 DMG_DIRECT
+        20:00:33.000| Damage. Victim: Bar, attacker: Foo, weapon 'Gun', damage: 25.0 DMG_DIRECT
+        20:00:33.000| Kill. Victim: Bar killer: Foo
+        20:02:00.000| ===== Gameplay finish, reason: no_cars, winner team 1, win reason: MORE_CARS_LEFT, battle time: 115.0 sec =====",
+    );
+    let game_ids = ingest_games(&conn, 11, &games).unwrap();
+
+    let filter: Filter = "attacker == Foo || killer == Foo".parse().unwrap();
+    let entries = export_entries(
+        &conn,
+        Some(game_ids[0]),
+        Some(&filter),
+        10,
+        &GameFilter::default(),
+    )
+    .unwrap();
+    let values: Vec<Option<f32>> = entries
+        .iter()
+        .map(|e| match e.message {
+            Payload::Damage(ref damage) => Some(damage.value),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(values, vec![Some(30.0), Some(20.0), Some(25.0), None]);
+
+    let entries = export_entries(&conn, None, None, 2, &GameFilter::default()).unwrap();
+    assert_eq!(entries.len(), 2);
+}
@@ -0,0 +1,35 @@
+#[macro_use]
+extern crate diesel_migrations;
+
+mod common;
+
+use diesel::prelude::*;
+
+use crossout_log_server::ingest::ingest_games;
+use crossout_log_server::schema::{kills, weapons};
+
+#[test]
+#[ignore = "requires a PostgreSQL database at DATABASE_URL"]
+fn test_ingest_kill_weapons_of_the_current_life() {
+    let conn = common::connect();
+    let games = common::games(
+        "20:00:00.000| ====== starting level 3: 'levels/maps/bad_rock' pvp ======
+        20:00:05.000| ===== Gameplay 'Pvp' started, map 'bad_rock' ======
+        20:00:05.000| player  0, uid 11, party 1, nickname: Foo, team: 1, bot: 0, ur: 5, mmHash: ab
+        20:00:05.000| player  1, uid 12, party 2, nickname: Bar, team: 2, bot: 0, ur: 6, mmHash: cd
+        20:00:30.000| Damage. Victim: Bar, attacker: Foo, weapon 'Gun', damage: 30.0 DMG_DIRECT
+        20:00:31.000| Kill. Victim: Bar killer: Foo
+        20:00:50.000| Kill. Victim: Bar killer: Foo
+        20:02:00.000| ===== Gameplay finish, reason: no_cars, winner team 1, win reason: MORE_CARS_LEFT, battle time: 115.0 sec =====",
+    );
+    ingest_games(&conn, 11, &games).unwrap();
+
+    // the second kill has no damage in the life of the victim, so its weapon is unknown
+    let weapons: Vec<Option<String>> = kills::table
+        .left_join(weapons::table)
+        .order(kills::kill_ts)
+        .select(weapons::name.nullable())
+        .load(&conn)
+        .unwrap();
+    assert_eq!(weapons, vec![Some("Gun".to_string()), None]);
+}
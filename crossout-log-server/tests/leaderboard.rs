@@ -0,0 +1,50 @@
+#[macro_use]
+extern crate diesel_migrations;
+
+mod common;
+
+use crossout_log_server::ingest::ingest_games;
+use crossout_log_server::stats::leaderboard::{leaderboard, LeaderboardFilter, Metric};
+
+#[test]
+#[ignore = "requires a PostgreSQL database at DATABASE_URL"]
+fn test_leaderboard_ranks_bots_apart() {
+    let conn = common::connect();
+    let games = common::games(
+        "20:00:00.000| ====== starting level 3: 'levels/maps/bad_rock' pvp ======
+        20:00:05.000| ===== Gameplay 'Pvp' started, map 'bad_rock' ======
+        20:00:05.000| player  0, uid 11, party 1, nickname: Foo, team: 1, bot: 0, ur: 5, mmHash: ab
+        20:00:05.000| player  1, uid 0, party 0, nickname: Bot1, team: 2, bot: 1, ur: 0, mmHash: 0
+        20:00:05.000| player  2, uid 0, party 0, nickname: Bot2, team: 2, bot: 1, ur: 0, mmHash: 0
+        20:00:30.000| Kill. Victim: Foo killer: Bot1
+        20:00:40.000| Kill. Victim: Bot1 killer: Bot2
+        20:00:50.000| Kill. Victim: Bot2 killer: Bot1
+        20:00:55.000| Kill. Victim: Bot1 killer: Bot1
+        20:02:00.000| ===== Gameplay finish, reason: no_cars, winner team 2, win reason: MORE_CARS_LEFT, battle time: 115.0 sec =====",
+    );
+    let game_ids = ingest_games(&conn, 11, &games).unwrap();
+    assert_eq!(game_ids.len(), 1);
+
+    let rows = leaderboard(&conn, Metric::Kills, &LeaderboardFilter::default()).unwrap();
+    let rows: Vec<(i64, &str, f64)> = rows
+        .iter()
+        .map(|r| (r.user_id, r.name.as_str(), r.value))
+        .collect();
+    assert_eq!(
+        rows,
+        vec![(0, "Bot1", 2.0), (0, "Bot2", 1.0), (11, "Foo", 0.0)]
+    );
+
+    // the suicide is a death, but no kill
+    let rows = leaderboard(&conn, Metric::KillDeath, &LeaderboardFilter::default()).unwrap();
+    let bot1 = rows.iter().find(|r| r.name == "Bot1").unwrap();
+    assert_eq!(bot1.value, 1.0);
+
+    let filter = LeaderboardFilter {
+        exclude_bots: true,
+        ..Default::default()
+    };
+    let rows = leaderboard(&conn, Metric::WinRate, &filter).unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!((rows[0].user_id, rows[0].value), (11, 0.0));
+}
@@ -0,0 +1,28 @@
+#[macro_use]
+extern crate diesel_migrations;
+
+mod common;
+
+use crossout_log_server::ingest::ingest_games;
+use crossout_log_server::stats::weapons::weapon_stats;
+use crossout_log_server::stats::GameFilter;
+
+#[test]
+#[ignore = "requires a PostgreSQL database at DATABASE_URL"]
+fn test_weapon_stats_without_self_damage() {
+    let conn = common::connect();
+    let games = common::games(
+        "20:00:00.000| ====== starting level 3: 'levels/maps/bad_rock' pvp ======
+        20:00:05.000| ===== Gameplay 'Pvp' started, map 'bad_rock' ======
+        20:00:05.000| player  0, uid 11, party 1, nickname: Foo, team: 1, bot: 0, ur: 5, mmHash: ab
+        20:00:05.000| player  1, uid 12, party 2, nickname: Bar, team: 2, bot: 0, ur: 6, mmHash: cd
+        20:00:30.000| Damage. Victim: Bar, attacker: Foo, weapon 'Mortar', damage: 30.0 DMG_BLAST
+        20:00:31.000| Damage. Victim: Foo, attacker: Foo, weapon 'Mortar', damage: 50.0 DMG_BLAST|DMG_FLAME
+        20:02:00.000| ===== Gameplay finish, reason: no_cars, winner team 1, win reason: MORE_CARS_LEFT, battle time: 115.0 sec =====",
+    );
+    ingest_games(&conn, 11, &games).unwrap();
+
+    let stats = weapon_stats(&conn, Some("Mortar"), &GameFilter::default()).unwrap();
+    assert_eq!((stats[0].hits, stats[0].damage), (1, 30.0));
+    assert!(!stats[0].damage_share_by_flag.contains_key("DMG_FLAME"));
+}
@@ -1,16 +1,26 @@
 #![feature(let_chains)]
 
-use std::io::{BufWriter, Write};
+use std::io::{BufReader, BufWriter, Write};
 use std::path::{Path, PathBuf};
 use std::{fs, io};
 
-use chrono::NaiveDateTime;
+use chrono::{Duration, NaiveDateTime};
 use clap::Parser;
 use parse::logs_in_dir;
+use sink::{write_sinks, EntrySink, SinkConfig};
 
+use crossout_log_common::analyze::{run_analyzers, AnalyzerRegistry};
+use crossout_log_common::filter::Filter;
+use crossout_log_common::game::assemble_games;
 use crossout_log_common::log::Entry;
+use crossout_log_common::owner::infer_owner;
+use crossout_log_common::redact::{RedactStats, Redactor};
+use crossout_log_common::testdrive::test_drives;
 
 mod parse;
+mod report;
+mod script;
+mod sink;
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
@@ -19,6 +29,18 @@ enum Args {
     File(FileArgs),
     /// Parses all logs in the sub directories. Path can be inferred
     Directory(DirectoryArgs),
+    /// Prints the scoreboards of the games in an object file
+    Report(ReportArgs),
+    /// Compares the damage output of the weapons across the test drives in an object file
+    Testdrive(TestDriveArgs),
+    /// Replaces the nicknames and ids in a combat.log or object file with pseudonyms
+    Redact(RedactArgs),
+    /// Runs analyzers over the entries of an object file in a single pass
+    Analyze(AnalyzeArgs),
+    /// Runs a Rhai script over the entries of an object file, a combat.log or a directory of logs
+    Script(ScriptArgs),
+    /// Prints the entries of an object file, a combat.log or a directory of logs as JSON lines
+    Dump(DumpArgs),
     // Watches all logs in the sub directories. Path can be inferred
 }
 
@@ -32,7 +54,13 @@ struct FileArgs {
     date: NaiveDateTime,
     /// The output object file
     #[clap(short, long)]
-    output: PathBuf,
+    output: Option<PathBuf>,
+    /// Additional outputs: `bin:<path>`, `json:<path>`, `stdout`, an http(s) upload url or `unix:<socket>`
+    #[clap(long = "sink")]
+    sinks: Vec<SinkConfig>,
+    /// Memory maps the log instead of reading it
+    #[clap(long)]
+    mmap: bool,
 }
 
 #[derive(Parser, Debug)]
@@ -42,13 +70,97 @@ struct DirectoryArgs {
     input: PathBuf,
     /// The output directory for object files
     #[clap(short, long)]
+    output: Option<PathBuf>,
+    /// Additional outputs: `bin:<path>`, `json:<path>`, `stdout`, an http(s) upload url or `unix:<socket>`
+    #[clap(long = "sink")]
+    sinks: Vec<SinkConfig>,
+    /// Memory maps the logs instead of reading them
+    #[clap(long)]
+    mmap: bool,
+}
+
+#[derive(Parser, Debug)]
+struct ReportArgs {
+    /// The object file written by the file or directory command
+    #[clap()]
+    input: PathBuf,
+    /// The user id of the player who wrote the logs. Inferred if omitted
+    #[clap(long)]
+    me: Option<usize>,
+    /// Only prints the scores of the player who wrote the logs
+    #[clap(long)]
+    only_me: bool,
+}
+
+#[derive(Parser, Debug)]
+struct TestDriveArgs {
+    /// The object file written by the file or directory command
+    #[clap()]
+    input: PathBuf,
+    /// The window in milliseconds in which the burst damage is measured
+    #[clap(short, long, default_value = "1000")]
+    burst_ms: i64,
+}
+
+#[derive(Parser, Debug)]
+struct RedactArgs {
+    /// The combat.log file, or the object file written by the file or directory command
+    #[clap()]
+    input: PathBuf,
+    /// The redacted output file
+    #[clap(short, long)]
     output: PathBuf,
+    /// The salt of the pseudonyms. Files redacted with the same salt share the pseudonyms. Random if omitted
+    #[clap(short, long)]
+    salt: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+struct AnalyzeArgs {
+    /// The object file written by the file or directory command
+    #[clap()]
+    input: PathBuf,
+    /// The analyzers to run: damage, kills or scores. All if omitted
+    #[clap(short, long)]
+    analyzer: Vec<String>,
+}
+
+#[derive(Parser, Debug)]
+struct ScriptArgs {
+    /// The Rhai script, defining `on_entry(entry, context)` and optionally `init()` and `finish()`
+    #[clap()]
+    script: PathBuf,
+    /// The object file, combat.log file or directory containing the logs
+    #[clap()]
+    input: PathBuf,
+    /// The date of the combat.log file
+    #[clap(short, long)]
+    date: Option<NaiveDateTime>,
+}
+
+#[derive(Parser, Debug)]
+struct DumpArgs {
+    /// The object file, combat.log file or directory containing the logs
+    #[clap()]
+    input: PathBuf,
+    /// The date of the combat.log file
+    #[clap(short, long)]
+    date: Option<NaiveDateTime>,
+    /// Only prints the entries matching the filter, e.g. `type == Damage && attacker == "Foo" && flags contains BLAST`
+    #[clap(long = "where")]
+    filter: Option<Filter>,
 }
 
 fn main() {
     if let Err(e) = match Args::parse() {
         Args::File(p) => parse_log(p),
         Args::Directory(d) => parse_logs_in_dir(d),
+        Args::Report(r) => report(r),
+        Args::Testdrive(t) => test_drive_report(t),
+        Args::Redact(r) => redact(r),
+        Args::Analyze(a) => analyze(a),
+        Args::Script(s) => run_script(s),
+        Args::Dump(d) => dump(d),
     } {
         println!("{}", e);
     }
@@ -58,13 +170,18 @@ fn parse_log(args: FileArgs) -> Result<(), Error> {
     if !args.input.is_file() {
         return Err(Error::FileNotFound(args.input));
     }
-    if args.output.parent().map(|p| p.is_dir()).unwrap_or(false) {
-        return Err(Error::DirNotFound(args.output));
+    if let Some(output) = args.output.as_ref().filter(|o| {
+        o.parent()
+            .is_some_and(|p| !p.as_os_str().is_empty() && !p.is_dir())
+    }) {
+        return Err(Error::DirNotFound(output.clone()));
     }
-    let (messages, errors) =
-        parse::parse_logs(vec![(args.input, args.date.date(), 0..usize::MAX)].into_iter());
-    write_output(&args.output, messages, errors)?;
-    Ok(())
+    let (messages, errors, diagnostics) = parse::parse_logs(
+        vec![(args.input, args.date.date(), 0..usize::MAX)].into_iter(),
+        args.mmap,
+    );
+    eprintln!("{}", diagnostics);
+    write_output(args.output, &args.sinks, &messages, &errors)
 }
 
 fn parse_logs_in_dir(args: DirectoryArgs) -> Result<(), Error> {
@@ -72,18 +189,166 @@ fn parse_logs_in_dir(args: DirectoryArgs) -> Result<(), Error> {
     if !input.is_dir() {
         return Err(Error::LogDirNotInferred);
     }
-    if !args.output.is_dir() {
-        fs::create_dir_all(&args.output)?;
+    let output = args.output.map(|mut output| {
+        output.push("combat.log.bin");
+        output
+    });
+    if let Some(dir) = output.as_ref().and_then(|o| o.parent()) {
+        fs::create_dir_all(dir)?;
     }
     let logs = logs_in_dir(input)?;
-    let (messages, errors) = parse::parse_logs(
+    let (messages, errors, diagnostics) = parse::parse_logs(
         logs.into_iter()
             .map(|(p, dt)| (p, dt.date(), 0..usize::MAX)),
+        args.mmap,
     );
+    eprintln!("{}", diagnostics);
+    write_output(output, &args.sinks, &messages, &errors)
+}
 
-    let mut output = args.output;
-    output.push("combat.log.bin");
-    write_output(&output, messages, errors)
+fn report(args: ReportArgs) -> Result<(), Error> {
+    let entries = read_input(&args.input)?;
+    let games = assemble_games(entries);
+    let owner = infer_owner(&games, args.me);
+    if args.only_me && owner.is_none() {
+        return Err(Error::OwnerNotInferred);
+    }
+    let stdout = io::stdout();
+    report::write_report(&games, owner, args.only_me, &mut stdout.lock())?;
+    Ok(())
+}
+
+fn test_drive_report(args: TestDriveArgs) -> Result<(), Error> {
+    let entries = read_input(&args.input)?;
+    let drives = test_drives(
+        &assemble_games(entries),
+        Duration::milliseconds(args.burst_ms),
+    );
+    let stdout = io::stdout();
+    report::write_test_drives(&drives, &mut stdout.lock())?;
+    Ok(())
+}
+
+fn analyze(args: AnalyzeArgs) -> Result<(), Error> {
+    let registry = AnalyzerRegistry::with_builtins();
+    let analyzers = if args.analyzer.is_empty() {
+        registry.create(registry.names())
+    } else {
+        registry.create(args.analyzer.iter().map(String::as_str))
+    }
+    .map_err(|name| Error::UnknownAnalyzer(name.to_string()))?;
+    let entries = read_input(&args.input)?;
+    let reports = run_analyzers(analyzers, &entries);
+    let stdout = io::stdout();
+    report::write_analyzer_reports(&reports, &mut stdout.lock())?;
+    Ok(())
+}
+
+fn run_script(args: ScriptArgs) -> Result<(), Error> {
+    let script = script::Script::from_file(&args.script)?;
+    let entries = read_entries(args.input, args.date)?;
+    let rows = script.run(&entries)?;
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for row in rows.iter() {
+        writeln!(out, "{}", script::format_row(row))?;
+    }
+    Ok(())
+}
+
+fn dump(args: DumpArgs) -> Result<(), Error> {
+    let entries = read_entries(args.input, args.date)?;
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let matching = entries
+        .iter()
+        .filter(|e| args.filter.as_ref().is_none_or(|f| f.matches(e)));
+    for entry in matching {
+        serde_json::to_writer(&mut out, entry)?;
+        out.write_all(b"\n")?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn redact(args: RedactArgs) -> Result<(), Error> {
+    if !args.input.is_file() {
+        return Err(Error::FileNotFound(args.input));
+    }
+    let salt = args.salt.unwrap_or_else(random_salt);
+    let redactor = Redactor::new(salt);
+    if args.input.extension().is_some_and(|e| e == "log") {
+        // damaged lines are repaired like when parsing, so their identities are redacted too
+        let log = fs::read(&args.input)?;
+        let diagnostics = parse::Diagnostics::default();
+        let mut stats = RedactStats::default();
+        let mut writer = BufWriter::new(fs::File::create(&args.output)?);
+        for line in log.split_inclusive(|&b| b == b'\n') {
+            let content = line.strip_suffix(b"\n").unwrap_or(line);
+            let content = content.strip_suffix(b"\r").unwrap_or(content);
+            if let Some(decoded) = parse::decode_line(content, &diagnostics) {
+                writer.write_all(redactor.redact_line(&decoded, &mut stats).as_bytes())?;
+            }
+            writer.write_all(&line[content.len()..])?;
+        }
+        writer.flush()?;
+        if stats.masked() > 0 {
+            eprintln!(
+                "Warning: {} lines masked, {} could not be parsed and {} had identities which were not found",
+                stats.masked(),
+                stats.unparsed,
+                stats.unreplaced
+            );
+        }
+    } else {
+        let mut entries = read_input(&args.input)?;
+        redactor.redact_entries(&mut entries);
+        let writer = fs::File::create(&args.output)?;
+        bincode::serialize_into(BufWriter::new(writer), &entries)?;
+    }
+    Ok(())
+}
+
+/// A salt unique to this run, so the pseudonyms cannot be matched with other files.
+fn random_salt() -> String {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    format!("{}-{}", nanos, std::process::id())
+}
+
+fn read_input(input: &Path) -> Result<Vec<Entry>, Error> {
+    if !input.is_file() {
+        return Err(Error::FileNotFound(input.to_path_buf()));
+    }
+    let reader = fs::File::open(input)?;
+    Ok(bincode::deserialize_from(BufReader::new(reader))?)
+}
+
+/// Reads the entries of an object file, or parses a combat.log of the date or all logs in a directory.
+fn read_entries(input: PathBuf, date: Option<NaiveDateTime>) -> Result<Vec<Entry>, Error> {
+    if input.is_dir() {
+        let logs = logs_in_dir(input)?;
+        let (entries, _, diagnostics) = parse::parse_logs(
+            logs.into_iter()
+                .map(|(p, dt)| (p, dt.date(), 0..usize::MAX)),
+            false,
+        );
+        eprintln!("{}", diagnostics);
+        Ok(entries)
+    } else if input.extension().is_some_and(|e| e == "log") {
+        if !input.is_file() {
+            return Err(Error::FileNotFound(input));
+        }
+        let date = date.ok_or(Error::DateRequired)?;
+        let (entries, _, diagnostics) =
+            parse::parse_logs(vec![(input, date.date(), 0..usize::MAX)].into_iter(), false);
+        eprintln!("{}", diagnostics);
+        Ok(entries)
+    } else {
+        read_input(&input)
+    }
 }
 
 fn amortized_logs_dir(dir: PathBuf) -> Result<PathBuf, Error> {
@@ -98,27 +363,39 @@ fn amortized_logs_dir(dir: PathBuf) -> Result<PathBuf, Error> {
     }
 }
 
-fn write_output(output: &Path, messages: Vec<Entry>, errors: Vec<String>) -> Result<(), Error> {
-    let writer = fs::File::create(output)?;
-    bincode::serialize_into(BufWriter::new(writer), &messages)?;
-
-    if !errors.is_empty() {
-        let writer = fs::File::create(output.with_extension("errors.log"))?;
-        let mut writer = BufWriter::new(writer);
-        for err in errors {
-            writer.write_all(format!("{}\n", err).as_bytes())?;
-        }
+/// Writes the entries to the output object file and all other sinks.
+fn write_output(
+    output: Option<PathBuf>,
+    sinks: &[SinkConfig],
+    messages: &[Entry],
+    errors: &[String],
+) -> Result<(), Error> {
+    let mut sinks: Vec<Box<dyn EntrySink>> = output
+        .map(SinkConfig::Bincode)
+        .iter()
+        .chain(sinks)
+        .map(SinkConfig::create)
+        .collect();
+    if sinks.is_empty() {
+        return Err(Error::NoOutput);
     }
-    Ok(())
+    write_sinks(&mut sinks, messages, errors)
 }
 
 #[derive(Debug)]
 pub enum Error {
     LogDirNotInferred,
+    OwnerNotInferred,
+    NoOutput,
+    UnknownAnalyzer(String),
+    DateRequired,
     FileNotFound(PathBuf),
     DirNotFound(PathBuf),
     File(io::Error),
     Ser(bincode::Error),
+    Json(serde_json::Error),
+    Upload(String),
+    Script(String),
 }
 
 impl std::error::Error for Error {}
@@ -127,9 +404,21 @@ impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Error::LogDirNotInferred => write!(f, "Log directory could not be inferred"),
+            Error::OwnerNotInferred => {
+                write!(
+                    f,
+                    "The player who wrote the logs could not be inferred, use --me"
+                )
+            }
             Error::FileNotFound(p) => write!(f, "File `{}` not found", p.display()),
             Error::DirNotFound(p) => write!(f, "Directory `{}` not found", p.display()),
+            Error::NoOutput => write!(f, "No output or sink given"),
+            Error::UnknownAnalyzer(name) => write!(f, "Unknown analyzer `{}`", name),
+            Error::DateRequired => write!(f, "The date of the combat.log is required, use --date"),
             Error::File(e) => write!(f, "{}", e),
+            Error::Json(e) => write!(f, "{}", e),
+            Error::Upload(e) => write!(f, "Upload failed: {}", e),
+            Error::Script(e) => write!(f, "Script failed: {}", e),
             _ => write!(f, "Unexpected error occurred"),
         }
     }
@@ -147,6 +436,12 @@ impl From<bincode::Error> for Error {
     }
 }
 
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -155,7 +450,9 @@ mod test {
     fn test_directory_logs() {
         parse_logs_in_dir(DirectoryArgs {
             input: "".into(),
-            output: "./publish".into(),
+            output: Some("./publish".into()),
+            sinks: Vec::new(),
+            mmap: false,
         })
         .expect("nope");
     }
@@ -1,24 +1,17 @@
 use std::{
-    fs,
-    io::{BufRead, BufReader},
+    fmt, fs,
+    io::{self, Read},
     ops::{Deref, Range},
     path::PathBuf,
-    sync::{
-        atomic::{AtomicUsize, Ordering},
-        Arc,
-    },
-    time::Duration,
+    sync::atomic::{AtomicUsize, Ordering},
 };
 
-use chrono::{NaiveDate, NaiveDateTime};
-use closure::closure;
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use crossbeam::queue::SegQueue;
 use crossbeam::thread;
-use crossbeam::{
-    channel::{unbounded, Sender},
-    queue::SegQueue,
-};
+use memmap2::Mmap;
 
-use crossout_log_common::log::{Entry, parse_entry};
+use crossout_log_common::log::{parse_entry, Entry};
 
 use crate::Error;
 
@@ -27,9 +20,11 @@ pub fn logs_in_dir(input: PathBuf) -> Result<Vec<(PathBuf, NaiveDateTime)>, Erro
     for dir in input
         .read_dir()?
         .flatten()
-        .filter(|sub| sub.file_type().map_or(false, |t| t.is_dir()))
+        .filter(|sub| sub.file_type().is_ok_and(|t| t.is_dir()))
     {
-        if let Some(dir_name) = dir.file_name().to_str() && let Ok(date) = NaiveDateTime::parse_from_str(dir_name, "%Y.%m.%d %H.%M.%S") {
+        if let Some(dir_name) = dir.file_name().to_str()
+            && let Ok(date) = NaiveDateTime::parse_from_str(dir_name, "%Y.%m.%d %H.%M.%S")
+        {
             let mut file_name = dir.path();
             file_name.push("combat.log");
             if file_name.exists() {
@@ -41,127 +36,290 @@ pub fn logs_in_dir(input: PathBuf) -> Result<Vec<(PathBuf, NaiveDateTime)>, Erro
     Ok(log_dirs)
 }
 
-pub fn parse_logs<
-    In: Iterator<Item = (PathBuf, NaiveDate, Range<usize>)>
-        + ExactSizeIterator<Item = (PathBuf, NaiveDate, Range<usize>)>,
->(
-    logs: In,
-) -> (Vec<Entry>, Vec<String>) {
-    let entries = Arc::new(SegQueue::new());
-    let errors = Arc::new(SegQueue::new());
-    io_cpu_upload_bus(
-        logs,
-        |(log, date, accept_lines), sender| {
-            if let Ok(file) = fs::File::open(log) {
-                let reader = BufReader::new(file);
-                for (pos, line) in reader.lines().flatten().enumerate() {
-                    if accept_lines.contains(&pos) {
-                        // collect log information for parser
-                        _ = sender.send((line, date));
-                    }
-                }
-            }
-        },
-        |(line, date)| {
-            // parse collection information
-            if let Ok((_, entry)) = parse_entry::<()>(date)(&line) {
-                Ok(Some(entry))
-            } else if !line.is_empty() {
-                Err(line.clone())
+/// Counts the lines read from the logs, and the lines repaired or skipped while reading.
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    pub lines: AtomicUsize,
+    pub entries: AtomicUsize,
+    /// Lines which could not be parsed, listed in the error log.
+    pub unparsed: AtomicUsize,
+    /// Lines with invalid UTF-8, decoded with replacement characters.
+    pub lossy: AtomicUsize,
+    /// Lines from which NUL bytes were removed. A crashed game leaves NUL-filled regions in the log.
+    pub nul_repaired: AtomicUsize,
+    /// Lines consisting of NUL bytes only.
+    pub nul_skipped: AtomicUsize,
+    /// Last lines of a log without line break which could not be parsed, usually cut off by a crash.
+    pub truncated: AtomicUsize,
+    /// Logs which could not be opened or read to the end.
+    pub io_errors: AtomicUsize,
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let count = |counter: &AtomicUsize| counter.load(Ordering::SeqCst);
+        write!(
+            f,
+            "{} lines, {} entries, {} unparsed, {} decoded lossy, {} with NUL bytes repaired, {} NUL lines skipped, {} truncated, {} IO errors",
+            count(&self.lines),
+            count(&self.entries),
+            count(&self.unparsed),
+            count(&self.lossy),
+            count(&self.nul_repaired),
+            count(&self.nul_skipped),
+            count(&self.truncated),
+            count(&self.io_errors)
+        )
+    }
+}
+
+/// Decodes a line read up to and including the line break. NUL bytes are removed and invalid UTF-8 is replaced. Returns `None` for lines of NUL bytes only.
+pub fn decode_line(bytes: &[u8], diagnostics: &Diagnostics) -> Option<String> {
+    let bytes = bytes.strip_suffix(b"\n").unwrap_or(bytes);
+    let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+    let mut bytes = bytes.to_vec();
+    if bytes.contains(&0) {
+        bytes.retain(|&b| b != 0);
+        if bytes.iter().all(u8::is_ascii_whitespace) {
+            diagnostics.nul_skipped.fetch_add(1, Ordering::SeqCst);
+            return None;
+        }
+        diagnostics.nul_repaired.fetch_add(1, Ordering::SeqCst);
+    }
+    match String::from_utf8(bytes) {
+        Ok(line) => Some(line),
+        Err(e) => {
+            diagnostics.lossy.fetch_add(1, Ordering::SeqCst);
+            Some(String::from_utf8_lossy(e.as_bytes()).into_owned())
+        }
+    }
+}
+
+/// The size of the newline-aligned chunks in which a log is parsed in parallel.
+const CHUNK_SIZE: usize = 1 << 20;
+/// A time stamp this many hours before the previous one belongs to the next day.
+const ROLLOVER_HOURS: i64 = 12;
+
+/// The bytes of a log, read or memory mapped.
+enum Content {
+    Read(Vec<u8>),
+    Mapped(Mmap),
+}
+
+impl Deref for Content {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Content::Read(bytes) => bytes,
+            Content::Mapped(map) => map,
+        }
+    }
+}
+
+fn read_log(path: &PathBuf, mmap: bool) -> io::Result<Content> {
+    let mut file = fs::File::open(path)?;
+    if mmap {
+        // SAFETY: the game only appends to its logs, and the mapping is read before the next session starts a new log
+        Ok(Content::Mapped(unsafe { Mmap::map(&file)? }))
+    } else {
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        Ok(Content::Read(bytes))
+    }
+}
+
+/// A part of a log ending with a line break or the end of the log.
+struct Chunk<'a> {
+    /// The zero-based number of the first line of the chunk in the log.
+    first_line: usize,
+    bytes: &'a [u8],
+}
+
+/// Splits the log into chunks of about the chunk size, so that no line is split.
+fn split_chunks(bytes: &[u8], chunk_size: usize) -> Vec<Chunk<'_>> {
+    let mut chunks = Vec::new();
+    let mut first_line = 0;
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        let end = match rest.get(chunk_size..) {
+            Some(tail) => tail
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(rest.len(), |p| chunk_size + p + 1),
+            None => rest.len(),
+        };
+        let (chunk, remainder) = rest.split_at(end);
+        chunks.push(Chunk {
+            first_line,
+            bytes: chunk,
+        });
+        first_line += chunk.iter().filter(|&&b| b == b'\n').count();
+        rest = remainder;
+    }
+    chunks
+}
+
+/// Parses the accepted lines of the chunk, dated with the date of the log. Unparsed lines are returned as errors.
+fn parse_chunk(
+    chunk: &Chunk,
+    date: NaiveDate,
+    accept_lines: &Range<usize>,
+    diagnostics: &Diagnostics,
+) -> Vec<Result<Entry, String>> {
+    let mut parsed = Vec::new();
+    let lines = chunk.bytes.split_inclusive(|&b| b == b'\n');
+    for (pos, bytes) in (chunk.first_line..).zip(lines) {
+        if !accept_lines.contains(&pos) {
+            continue;
+        }
+        diagnostics.lines.fetch_add(1, Ordering::SeqCst);
+        let line = match decode_line(bytes, diagnostics) {
+            Some(line) => line,
+            None => continue,
+        };
+        let entry = parse_entry::<()>(date)(&line).ok().map(|(_, entry)| entry);
+        if let Some(entry) = entry {
+            diagnostics.entries.fetch_add(1, Ordering::SeqCst);
+            parsed.push(Ok(entry));
+        } else if !line.trim().is_empty() {
+            if bytes.ends_with(b"\n") {
+                diagnostics.unparsed.fetch_add(1, Ordering::SeqCst);
             } else {
-                Ok(None)
-            }
-        },
-        500,
-        |buf| {
-            while let Some(entry) = buf.pop() {
-                entries.push(entry);
+                diagnostics.truncated.fetch_add(1, Ordering::SeqCst);
             }
-            Ok(())
-        },
-        |e| {
-            errors.push(e);
-        },
-    );
-    (collect_segq(entries), collect_segq(errors))
+            parsed.push(Err(line));
+        }
+    }
+    parsed
+}
+
+/// Dates the time stamps of a log after midnight with the following days. A log only contains the time of day.
+#[derive(Debug, Default)]
+struct Rollover {
+    days: i64,
+    last: Option<NaiveTime>,
 }
 
-fn collect_segq<T, Q: Deref<Target = SegQueue<T>>>(q: Q) -> Vec<T> {
-    let mut clone = Vec::with_capacity(q.len());
-    while let Some(item) = q.pop() {
-        clone.push(item);
+impl Rollover {
+    fn date(&mut self, time_stamp: NaiveDateTime) -> NaiveDateTime {
+        let time = time_stamp.time();
+        if self
+            .last
+            .is_some_and(|last| last - time > Duration::hours(ROLLOVER_HOURS))
+        {
+            self.days += 1;
+        }
+        self.last = Some(time);
+        time_stamp + Duration::days(self.days)
     }
-    clone
 }
 
-pub fn io_cpu_upload_bus<
-    In: Iterator<Item = T> + ExactSizeIterator<Item = T>,
-    T: Send,
-    U: Send,
-    V: Send,
-    E: Send,
-    F: Fn(T, Sender<U>) + Send + Copy,
-    G: Fn(U) -> Result<Option<V>, E> + Send + Copy,
-    H: Fn(Arc<SegQueue<V>>) -> Result<(), E> + Send + Copy,
-    I: Fn(E) + Send + Copy,
->(
-    input: In,
-    io: F,
-    cpu: G,
-    upload_threshold: usize,
-    upload: H,
-    error_handler: I,
-) {
-    let cpu_threads = 1.max(num_cpus::get() - 1); // cpu count - one thread reserved for upload
-    let (sender, receiver) = unbounded(); // work queue fed by io, consumed by cpu heavy tasks
-    let io_active = Arc::new(AtomicUsize::new(input.len())); // counts active io threads
-    let cpu_active = Arc::new(AtomicUsize::new(cpu_threads)); // counts active cpu threads
-    let buf = Arc::new(SegQueue::new()); // upload buffer
+/// Parses the chunks of a log in parallel, and returns the parsed lines in order.
+fn parse_content(
+    content: &[u8],
+    date: NaiveDate,
+    accept_lines: &Range<usize>,
+    diagnostics: &Diagnostics,
+) -> Vec<Result<Entry, String>> {
+    let chunks = split_chunks(content, CHUNK_SIZE);
+    let work = SegQueue::new();
+    (0..chunks.len()).for_each(|i| work.push(i));
+    let parsed = SegQueue::new();
     thread::scope(|scope| {
-        for item in input {
-            scope.spawn(closure!(clone io_active, clone sender, |_|{
-                io(item, sender);
-                io_active.fetch_sub(1, Ordering::SeqCst);
-            }));
-        }
-        for _ in 0..cpu_threads {
-            scope.spawn(
-                closure!(clone io_active, clone cpu_active, clone buf, clone receiver, |_| {
-                    while io_active.load(Ordering::SeqCst) != 0 {
-                        while let Ok(item) = receiver.try_recv() {
-                            match cpu(item) {
-                                Ok(v) => if let Some(v) = v {
-                                    buf.push(v);
-                                },
-                                Err(e) => error_handler(e),
-                            }
-                        }
-                        std::thread::sleep(Duration::new(0,1)); // encourage ctx change on io pipe fail
-                    }
-                    cpu_active.fetch_sub(1, Ordering::SeqCst);
-                }),
-            );
-        }
-        scope.spawn(move |_| {
-            while cpu_active.load(Ordering::SeqCst) != 0 {
-                if buf.len() >= upload_threshold {
-                    // upload when threshold is exceeded
-                    match upload(buf.clone()) {
-                        Ok(_) => {}
-                        Err(e) => error_handler(e),
-                    }
+        for _ in 0..num_cpus::get().min(chunks.len()) {
+            scope.spawn(|_| {
+                while let Some(i) = work.pop() {
+                    parsed.push((i, parse_chunk(&chunks[i], date, accept_lines, diagnostics)));
                 }
-                std::thread::sleep(Duration::new(0, 1)); // encourage ctx change
+            });
+        }
+    })
+    .unwrap();
+
+    let mut parsed: Vec<_> = std::iter::from_fn(|| parsed.pop()).collect();
+    parsed.sort_unstable_by_key(|(i, _)| *i);
+    parsed.into_iter().flat_map(|(_, lines)| lines).collect()
+}
+
+/// Parses the logs one after another, and returns the entries in order of the logs and lines, the unparsed lines and the diagnostics.
+///
+/// Each log is split into chunks which are parsed by all threads, so a single large log is parsed as fast as many small ones. The chunks are stitched back in order, which keeps the assists after their kill, and dates the entries after midnight.
+/// Only one log is held in memory at a time.
+pub fn parse_logs(
+    logs: impl Iterator<Item = (PathBuf, NaiveDate, Range<usize>)>,
+    mmap: bool,
+) -> (Vec<Entry>, Vec<String>, Diagnostics) {
+    let diagnostics = Diagnostics::default();
+    let mut errors = Vec::new();
+    let mut entries = Vec::new();
+    for (path, date, accept_lines) in logs {
+        let content = match read_log(&path, mmap) {
+            Ok(content) => content,
+            Err(e) => {
+                diagnostics.io_errors.fetch_add(1, Ordering::SeqCst);
+                errors.push(format!("{}: {}", path.display(), e));
+                continue;
             }
-            // all cpu threads are terminated
-            if buf.len() != 0 {
-                // upload remainder
-                match upload(buf.clone()) {
-                    Ok(_) => {}
-                    Err(e) => error_handler(e),
+        };
+        let mut rollover = Rollover::default();
+        for line in parse_content(&content, date, &accept_lines, &diagnostics) {
+            match line {
+                Ok(mut entry) => {
+                    entry.time_stamp = rollover.date(entry.time_stamp);
+                    entries.push(entry);
                 }
+                Err(line) => errors.push(line),
             }
-        });
-    })
-    .unwrap();
+        }
+    }
+    (entries, errors, diagnostics)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_decode_damaged_lines() {
+        let diagnostics = Diagnostics::default();
+        assert_eq!(
+            decode_line(
+                b"\x00\x00\x0020:00:00.000| Kill. Victim: Foo killer: Bar\r\n",
+                &diagnostics
+            ),
+            Some("20:00:00.000| Kill. Victim: Foo killer: Bar".to_string())
+        );
+        assert_eq!(decode_line(b"\0\0\0\0\n", &diagnostics), None);
+        assert_eq!(
+            decode_line(b"Kill. Victim: F\xffo", &diagnostics),
+            Some("Kill. Victim: F\u{fffd}o".to_string())
+        );
+        assert_eq!(diagnostics.nul_repaired.load(Ordering::SeqCst), 1);
+        assert_eq!(diagnostics.nul_skipped.load(Ordering::SeqCst), 1);
+        assert_eq!(diagnostics.lossy.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_split_chunks_on_lines() {
+        let log = b"23:59:59.000| Kill. Victim: Foo killer: Bar\n00:00:01.000| Kill. Victim: Bar killer: Foo\n00:00:02.000| Kill";
+        let chunks = split_chunks(log, 10);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(
+            chunks.iter().map(|c| c.first_line).collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+
+        let diagnostics = Diagnostics::default();
+        let date = NaiveDate::from_ymd_opt(2022, 6, 1).unwrap();
+        let mut rollover = Rollover::default();
+        let dates: Vec<_> = chunks
+            .iter()
+            .flat_map(|c| parse_chunk(c, date, &(0..usize::MAX), &diagnostics))
+            .filter_map(Result::ok)
+            .map(|e| rollover.date(e.time_stamp).date())
+            .collect();
+        assert_eq!(dates, vec![date, date.succ_opt().unwrap()]);
+        assert_eq!(diagnostics.truncated.load(Ordering::SeqCst), 1);
+    }
 }
@@ -0,0 +1,171 @@
+use std::io::{self, Write};
+
+use crossout_log_common::analyze::Report;
+use crossout_log_common::game::Game;
+use crossout_log_common::highlights::{detect_highlights, HighlightConfig, HighlightKind};
+use crossout_log_common::testdrive::TestDrive;
+
+/// Writes the scoreboards and highlights of all rounds of the games. The row of the log owner is marked with `*`, with `only_owner` the other rows are omitted.
+pub fn write_report(
+    games: &[Game],
+    owner: Option<usize>,
+    only_owner: bool,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let config = HighlightConfig::default();
+    if let Some(user_id) = owner {
+        let nick_name = games
+            .iter()
+            .flat_map(|g| g.rounds.iter())
+            .filter_map(|r| r.owner(user_id))
+            .next_back()
+            .map_or("unknown", |s| s.nick_name.as_str());
+        writeln!(out, "log owner {} ({})", nick_name, user_id)?;
+    }
+    for game in games.iter().filter(|g| !g.rounds.is_empty()) {
+        writeln!(
+            out,
+            "{} {} on {}{}",
+            game.start,
+            game.game_mode,
+            game.map,
+            if game.test_drive { " (test drive)" } else { "" }
+        )?;
+        for round in game.rounds.iter() {
+            let board = round.scoreboard();
+            let winner = round.finish.as_ref().map(|f| f.winning_team);
+            writeln!(
+                out,
+                "  round {} ({:.0} sec)",
+                round.round_no,
+                round.duration_sec().unwrap_or_default()
+            )?;
+            let mut teams: Vec<u8> = board.players.iter().map(|p| p.team).collect();
+            teams.sort_unstable();
+            teams.dedup();
+            for team in teams {
+                let parties: Vec<String> = round
+                    .party_sizes(team)
+                    .iter()
+                    .map(usize::to_string)
+                    .collect();
+                writeln!(
+                    out,
+                    "    team {}{}, parties {}",
+                    team,
+                    if winner == Some(team) { " (won)" } else { "" },
+                    parties.join("+")
+                )?;
+                writeln!(
+                    out,
+                    "      {:<24} {:>6} {:>3} {:>3} {:>3} {:>8} {:>8} {:>6}",
+                    "player", "score", "k", "d", "a", "dealt", "received", "alive"
+                )?;
+                for player in board.team(team) {
+                    let own = !player.bot && owner == Some(player.user_id);
+                    if only_owner && !own {
+                        continue;
+                    }
+                    writeln!(
+                        out,
+                        "    {} {:<24} {:>6.0} {:>3} {:>3} {:>3} {:>8.0} {:>8.0} {:>6.0}",
+                        if own { '*' } else { ' ' },
+                        player.nick_name,
+                        player.score,
+                        player.kills,
+                        player.deaths,
+                        player.assists,
+                        player.damage_dealt,
+                        player.damage_received,
+                        player.survival_sec
+                    )?;
+                }
+            }
+            for highlight in detect_highlights(round, &config) {
+                let detail = match (highlight.kind, highlight.other) {
+                    (HighlightKind::MultiKill | HighlightKind::Streak, _) => {
+                        format!(" of {} kills", highlight.kills)
+                    }
+                    (HighlightKind::FirstBlood, Some(victim)) => format!(" on {}", victim),
+                    (HighlightKind::Trade, Some(killer)) => format!(" of {}", killer),
+                    _ => String::new(),
+                };
+                writeln!(
+                    out,
+                    "    {} {} by {}{}",
+                    highlight.time_stamp.time(),
+                    highlight.kind,
+                    highlight.player,
+                    detail
+                )?;
+            }
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Writes a table comparing the damage output of each weapon across the test drives.
+pub fn write_test_drives(drives: &[TestDrive], out: &mut impl Write) -> io::Result<()> {
+    for (no, drive) in drives.iter().enumerate() {
+        writeln!(out, "#{} test drive at {}", no + 1, drive.start)?;
+    }
+    let mut weapons: Vec<&str> = drives
+        .iter()
+        .flat_map(|d| d.weapons.iter().map(|w| w.weapon.as_str()))
+        .collect();
+    weapons.sort_unstable();
+    weapons.dedup();
+    writeln!(
+        out,
+        "{:<32} {:>3} {:>5} {:>9} {:>7} {:>8} {:>8}  flags",
+        "weapon", "#", "hits", "damage", "window", "dps", "burst"
+    )?;
+    for weapon in weapons {
+        for (no, drive) in drives.iter().enumerate() {
+            if let Some(bench) = drive.weapons.iter().find(|w| w.weapon == weapon) {
+                let flags: Vec<String> = bench
+                    .damage_by_flag
+                    .iter()
+                    .map(|(flag, damage)| format!("{} {:.0}%", flag, damage * 100.0 / bench.damage))
+                    .collect();
+                let dps = bench
+                    .dps
+                    .map_or_else(|| "-".to_string(), |dps| format!("{:.1}", dps));
+                writeln!(
+                    out,
+                    "{:<32} {:>3} {:>5} {:>9.0} {:>7.1} {:>8} {:>8.0}  {}",
+                    weapon,
+                    no + 1,
+                    bench.hits,
+                    bench.damage,
+                    bench.window_sec(),
+                    dps,
+                    bench.burst,
+                    flags.join(", ")
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes the analyzer reports as tables, one row per key.
+pub fn write_analyzer_reports(reports: &[Report], out: &mut impl Write) -> io::Result<()> {
+    for report in reports {
+        write!(out, "{:<24}", report.name)?;
+        for column in report.columns.iter() {
+            write!(out, " {:>10}", column)?;
+        }
+        writeln!(out)?;
+        for (key, values) in report.rows.iter() {
+            write!(out, "{:<24}", key)?;
+            for value in values {
+                write!(out, " {:>10.1}", value)?;
+            }
+            writeln!(out)?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,189 @@
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+
+use rhai::serde::to_dynamic;
+use rhai::{Array, CallFnOptions, Dynamic, Engine, FuncArgs, Map, Scope, AST};
+
+use crossout_log_common::analyze::MatchContext;
+use crossout_log_common::log::{Entry, Payload};
+
+use crate::Error;
+
+/// A Rhai script run over the entries in order of time.
+///
+/// The top level statements run once before the first entry. The script may define
+/// - `fn init()` returning the initial state, an empty map if omitted,
+/// - `fn on_entry(entry, context)` called for every entry,
+/// - `fn finish()` called after the last entry.
+///
+/// The functions access the state as `this`, and call `emit(row)` to output a row: an array of values, or a single value.
+/// An entry is a map with the `kind` of the payload (`"Damage"`, `"Kill"`, ...), the `time_stamp`, the `time` in seconds since the epoch and the fields of the payload.
+/// The context is a map with the `game_start`, `game_mode`, `map`, `round_no`, `test_drive` and the `roster` of the round.
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    rows: Rc<RefCell<Vec<Array>>>,
+}
+
+impl Script {
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let source = std::fs::read_to_string(path)?;
+        Self::new(&source)
+    }
+
+    pub fn new(source: &str) -> Result<Self, Error> {
+        let mut engine = Engine::new();
+        let rows = Rc::new(RefCell::new(Vec::new()));
+        let emitted = rows.clone();
+        engine.register_fn("emit", move |row: Dynamic| {
+            let row = if row.is_array() {
+                row.cast::<Array>()
+            } else {
+                vec![row]
+            };
+            emitted.borrow_mut().push(row);
+        });
+        let ast = engine
+            .compile(source)
+            .map_err(|e| Error::Script(e.to_string()))?;
+        Ok(Self { engine, ast, rows })
+    }
+
+    /// Runs the script over the entries and returns the emitted rows.
+    pub fn run<'a>(
+        &self,
+        entries: impl IntoIterator<Item = &'a Entry>,
+    ) -> Result<Vec<Array>, Error> {
+        let mut scope = Scope::new();
+        self.engine
+            .run_ast_with_scope(&mut scope, &self.ast)
+            .map_err(|e| Error::Script(e.to_string()))?;
+        let mut state = Dynamic::from_map(Map::new());
+        if self.has_fn("init", 0) {
+            state = self.call(&mut scope, &mut state, "init", ())?;
+        }
+        if self.has_fn("on_entry", 2) {
+            let mut context = MatchContext::default();
+            for entry in entries {
+                context.update(entry);
+                let args = (entry_to_dynamic(entry)?, context_to_dynamic(&context)?);
+                let _ = self.call(&mut scope, &mut state, "on_entry", args)?;
+                if let Payload::TestFinish = entry.message {
+                    context.test_drive = false;
+                }
+            }
+        }
+        if self.has_fn("finish", 0) {
+            let _ = self.call(&mut scope, &mut state, "finish", ())?;
+        }
+        Ok(self.rows.take())
+    }
+
+    fn has_fn(&self, name: &str, params: usize) -> bool {
+        self.ast
+            .iter_functions()
+            .any(|f| f.name == name && f.params.len() == params)
+    }
+
+    fn call(
+        &self,
+        scope: &mut Scope,
+        state: &mut Dynamic,
+        name: &str,
+        args: impl FuncArgs,
+    ) -> Result<Dynamic, Error> {
+        let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(state);
+        self.engine
+            .call_fn_with_options(options, scope, &self.ast, name, args)
+            .map_err(|e| Error::Script(e.to_string()))
+    }
+}
+
+/// The values of the row separated by tabs.
+pub fn format_row(row: &Array) -> String {
+    row.iter()
+        .map(|value| value.to_string())
+        .collect::<Vec<_>>()
+        .join("\t")
+}
+
+/// The entry as a flat map of the kind, the time and the fields of the payload.
+fn entry_to_dynamic(entry: &Entry) -> Result<Dynamic, Error> {
+    let mut map = Map::new();
+    // unit variants are serialized as their name, others as a map of the name to the fields
+    let payload = to_dynamic(&entry.message).map_err(|e| Error::Script(e.to_string()))?;
+    if payload.is_map() {
+        for (kind, fields) in payload.cast::<Map>() {
+            map.insert("kind".into(), kind.as_str().into());
+            if fields.is_map() {
+                map.extend(fields.cast::<Map>());
+            }
+        }
+    } else {
+        map.insert("kind".into(), payload);
+    }
+    map.insert("time_stamp".into(), entry.time_stamp.to_string().into());
+    map.insert("time".into(), time_sec(entry.time_stamp).into());
+    Ok(map.into())
+}
+
+fn context_to_dynamic(context: &MatchContext) -> Result<Dynamic, Error> {
+    let mut map = Map::new();
+    map.insert(
+        "game_start".into(),
+        context
+            .game_start
+            .map_or(Dynamic::UNIT, |t| time_sec(t).into()),
+    );
+    map.insert("game_mode".into(), context.game_mode.clone().into());
+    map.insert("map".into(), context.map.clone().into());
+    map.insert("round_no".into(), (context.round_no as i64).into());
+    map.insert("test_drive".into(), context.test_drive.into());
+    map.insert(
+        "roster".into(),
+        to_dynamic(&context.roster).map_err(|e| Error::Script(e.to_string()))?,
+    );
+    Ok(map.into())
+}
+
+fn time_sec(time_stamp: chrono::NaiveDateTime) -> f64 {
+    time_stamp.and_utc().timestamp_millis() as f64 / 1000.0
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+    use crossout_log_common::log::parse_entry;
+
+    use super::*;
+
+    #[test]
+    fn test_run_script() {
+        let date = NaiveDate::from_ymd_opt(2022, 6, 1).unwrap();
+        let entries: Vec<Entry> = "20:00:05.000| ===== Gameplay 'Pvp' started, map 'bad_rock' ======
+            20:00:30.000| Damage. Victim: Bar, attacker: Foo, weapon 'Hurricane', damage: 30.0 DMG_DIRECT
+            20:00:31.000| Damage. Victim: Bar, attacker: Foo, weapon 'Gun', damage: 20.0 DMG_DIRECT
+            20:00:32.000| Damage. Victim: Bar, attacker: Foo, weapon 'Hurricane', damage: 12.5 DMG_DIRECT"
+            .lines()
+            .map(|line| parse_entry::<()>(date)(line.trim()).unwrap().1)
+            .collect();
+        let script = Script::new(
+            r#"
+            fn init() { #{ total: 0.0 } }
+            fn on_entry(entry, context) {
+                if entry.kind == "Damage" && entry.weapon == "Hurricane" {
+                    this.total += entry.value;
+                    emit([context.map, entry.value]);
+                }
+            }
+            fn finish() { emit(this.total); }
+            "#,
+        )
+        .unwrap();
+        let rows = script.run(&entries).unwrap();
+        let rows: Vec<String> = rows.iter().map(format_row).collect();
+        assert_eq!(rows, vec!["bad_rock\t30.0", "bad_rock\t12.5", "42.5"]);
+        assert!(Script::new("fn on_entry(entry, context) {").is_err());
+    }
+}
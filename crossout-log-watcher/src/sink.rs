@@ -0,0 +1,329 @@
+use std::fs;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crossout_log_common::log::Entry;
+
+use crate::Error;
+
+/// The number of entries passed to a sink at once.
+pub const BATCH_SIZE: usize = 1000;
+
+/// A destination of the parsed entries. Entries are written in batches between `open` and `finish`.
+pub trait EntrySink {
+    fn open(&mut self) -> Result<(), Error>;
+    fn write_batch(&mut self, entries: &[Entry]) -> Result<(), Error>;
+    /// Completes the output. `errors` are the lines which could not be parsed.
+    fn finish(&mut self, errors: &[String]) -> Result<(), Error>;
+}
+
+/// A sink given on the command line:
+/// `bin:<path>` or a plain path for an object file, `json:<path>` for JSON lines, `stdout` or `-` for JSON lines on the standard output,
+/// an `http://` or `https://` url to upload an object file to, and `unix:<path>` for JSON lines sent to a Unix domain socket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SinkConfig {
+    Bincode(PathBuf),
+    Json(PathBuf),
+    Stdout,
+    Http(String),
+    Unix(PathBuf),
+}
+
+impl FromStr for SinkConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "stdout" || s == "-" {
+            Ok(SinkConfig::Stdout)
+        } else if s.starts_with("http://") || s.starts_with("https://") {
+            Ok(SinkConfig::Http(s.to_string()))
+        } else if let Some(path) = s.strip_prefix("unix:") {
+            Ok(SinkConfig::Unix(path.into()))
+        } else if let Some(path) = s.strip_prefix("json:") {
+            Ok(SinkConfig::Json(path.into()))
+        } else if let Some(path) = s.strip_prefix("bin:") {
+            Ok(SinkConfig::Bincode(path.into()))
+        } else if s.is_empty() {
+            Err("Empty sink".to_string())
+        } else {
+            Ok(SinkConfig::Bincode(s.into()))
+        }
+    }
+}
+
+impl SinkConfig {
+    pub fn create(&self) -> Box<dyn EntrySink> {
+        match self {
+            SinkConfig::Bincode(path) => Box::new(BincodeSink::new(path.clone())),
+            SinkConfig::Json(path) => Box::new(JsonSink::file(path.clone())),
+            SinkConfig::Stdout => Box::new(JsonSink::stdout()),
+            SinkConfig::Http(url) => Box::new(HttpSink::new(url.clone())),
+            SinkConfig::Unix(path) => Box::new(JsonSink::unix(path.clone())),
+        }
+    }
+}
+
+/// Writes the entries to all sinks in batches.
+pub fn write_sinks(
+    sinks: &mut [Box<dyn EntrySink>],
+    entries: &[Entry],
+    errors: &[String],
+) -> Result<(), Error> {
+    for sink in sinks.iter_mut() {
+        sink.open()?;
+    }
+    for batch in entries.chunks(BATCH_SIZE) {
+        for sink in sinks.iter_mut() {
+            sink.write_batch(batch)?;
+        }
+    }
+    for sink in sinks.iter_mut() {
+        sink.finish(errors)?;
+    }
+    Ok(())
+}
+
+/// Writes the lines which could not be parsed next to the output file.
+fn write_errors(output: &Path, errors: &[String]) -> Result<(), Error> {
+    if !errors.is_empty() {
+        let writer = fs::File::create(output.with_extension("errors.log"))?;
+        let mut writer = BufWriter::new(writer);
+        for err in errors {
+            writer.write_all(format!("{}\n", err).as_bytes())?;
+        }
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+/// The error of a sink written before it was opened.
+fn not_open() -> Error {
+    Error::File(io::Error::new(
+        io::ErrorKind::NotConnected,
+        "Sink is not open",
+    ))
+}
+
+/// Serializes a `Vec<Entry>` with bincode one batch at a time. The vector is prefixed with its length as `u64`, which is only known at the end, so a placeholder is written first.
+struct BincodeWriter<W> {
+    writer: W,
+    len: u64,
+}
+
+impl<W: Write + Seek> BincodeWriter<W> {
+    fn new(mut writer: W) -> Result<Self, Error> {
+        writer.write_all(&0u64.to_le_bytes())?;
+        Ok(Self { writer, len: 0 })
+    }
+
+    fn write_batch(&mut self, entries: &[Entry]) -> Result<(), Error> {
+        for entry in entries {
+            bincode::serialize_into(&mut self.writer, entry)?;
+        }
+        self.len += entries.len() as u64;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<W, Error> {
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.writer.write_all(&self.len.to_le_bytes())?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// An object file, a bincode serialized `Vec<Entry>`, as read by the report commands and the server.
+pub struct BincodeSink {
+    path: PathBuf,
+    writer: Option<BincodeWriter<BufWriter<fs::File>>>,
+}
+
+impl BincodeSink {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path, writer: None }
+    }
+}
+
+impl EntrySink for BincodeSink {
+    fn open(&mut self) -> Result<(), Error> {
+        let file = BufWriter::new(fs::File::create(&self.path)?);
+        self.writer = Some(BincodeWriter::new(file)?);
+        Ok(())
+    }
+
+    fn write_batch(&mut self, entries: &[Entry]) -> Result<(), Error> {
+        self.writer
+            .as_mut()
+            .ok_or_else(not_open)?
+            .write_batch(entries)
+    }
+
+    fn finish(&mut self, errors: &[String]) -> Result<(), Error> {
+        self.writer.take().ok_or_else(not_open)?.finish()?;
+        write_errors(&self.path, errors)
+    }
+}
+
+/// JSON lines, one entry per line, written to a file, the standard output or a Unix domain socket.
+pub struct JsonSink {
+    target: JsonTarget,
+    writer: Option<Box<dyn Write>>,
+}
+
+enum JsonTarget {
+    File(PathBuf),
+    Stdout,
+    Unix(PathBuf),
+}
+
+impl JsonSink {
+    pub fn file(path: PathBuf) -> Self {
+        Self {
+            target: JsonTarget::File(path),
+            writer: None,
+        }
+    }
+
+    pub fn stdout() -> Self {
+        Self {
+            target: JsonTarget::Stdout,
+            writer: None,
+        }
+    }
+
+    pub fn unix(path: PathBuf) -> Self {
+        Self {
+            target: JsonTarget::Unix(path),
+            writer: None,
+        }
+    }
+}
+
+impl EntrySink for JsonSink {
+    fn open(&mut self) -> Result<(), Error> {
+        let writer: Box<dyn Write> = match self.target {
+            JsonTarget::File(ref path) => Box::new(BufWriter::new(fs::File::create(path)?)),
+            JsonTarget::Stdout => Box::new(BufWriter::new(io::stdout())),
+            #[cfg(unix)]
+            JsonTarget::Unix(ref path) => Box::new(BufWriter::new(UnixStream::connect(path)?)),
+            #[cfg(not(unix))]
+            JsonTarget::Unix(_) => {
+                return Err(Error::File(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "Unix domain sockets are not supported on this platform",
+                )))
+            }
+        };
+        self.writer = Some(writer);
+        Ok(())
+    }
+
+    fn write_batch(&mut self, entries: &[Entry]) -> Result<(), Error> {
+        let writer = self.writer.as_mut().ok_or_else(not_open)?;
+        for entry in entries {
+            serde_json::to_writer(&mut *writer, entry)?;
+            writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, errors: &[String]) -> Result<(), Error> {
+        if let Some(mut writer) = self.writer.take() {
+            writer.flush()?;
+        }
+        match self.target {
+            JsonTarget::File(ref path) => write_errors(path, errors),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Uploads an object file to the `/api/upload` endpoint of the crossout-log-server.
+pub struct HttpSink {
+    url: String,
+    body: Option<BincodeWriter<io::Cursor<Vec<u8>>>>,
+}
+
+impl HttpSink {
+    pub fn new(url: String) -> Self {
+        Self { url, body: None }
+    }
+}
+
+impl EntrySink for HttpSink {
+    fn open(&mut self) -> Result<(), Error> {
+        self.body = Some(BincodeWriter::new(io::Cursor::new(Vec::new()))?);
+        Ok(())
+    }
+
+    fn write_batch(&mut self, entries: &[Entry]) -> Result<(), Error> {
+        self.body
+            .as_mut()
+            .ok_or_else(not_open)?
+            .write_batch(entries)
+    }
+
+    fn finish(&mut self, _errors: &[String]) -> Result<(), Error> {
+        let body = self
+            .body
+            .take()
+            .ok_or_else(not_open)?
+            .finish()?
+            .into_inner();
+        ureq::post(&self.url)
+            .set("Content-Type", "application/octet-stream")
+            .send_bytes(&body)
+            .map_err(|e| Error::Upload(e.to_string()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_sink_config() {
+        assert_eq!("-".parse(), Ok(SinkConfig::Stdout));
+        assert_eq!("out.bin".parse(), Ok(SinkConfig::Bincode("out.bin".into())));
+        assert_eq!(
+            "json:out.json".parse(),
+            Ok(SinkConfig::Json("out.json".into()))
+        );
+        assert_eq!(
+            "unix:/tmp/logs.sock".parse(),
+            Ok(SinkConfig::Unix("/tmp/logs.sock".into()))
+        );
+        assert_eq!(
+            "http://localhost:8080/api/upload?uploader=12".parse(),
+            Ok(SinkConfig::Http(
+                "http://localhost:8080/api/upload?uploader=12".to_string()
+            ))
+        );
+        assert!("".parse::<SinkConfig>().is_err());
+    }
+
+    #[test]
+    fn test_write_bincode_in_batches() {
+        let date = chrono::NaiveDate::from_ymd_opt(2022, 6, 1).unwrap();
+        let entries: Vec<Entry> = (0..5)
+            .map(|i| {
+                let line = format!("20:00:0{}.000| Kill. Victim: Bar killer: Foo", i);
+                let (_, entry) = crossout_log_common::log::parse_entry::<()>(date)(&line).unwrap();
+                entry
+            })
+            .collect();
+        let mut writer = BincodeWriter::new(io::Cursor::new(Vec::new())).unwrap();
+        for batch in entries.chunks(2) {
+            writer.write_batch(batch).unwrap();
+        }
+        let bytes = writer.finish().unwrap().into_inner();
+        assert_eq!(bytes, bincode::serialize(&entries).unwrap());
+
+        let mut sink = JsonSink::stdout();
+        assert!(sink.write_batch(&entries).is_err());
+    }
+}